@@ -0,0 +1,65 @@
+// Débit de l'émulation sans limitation de vitesse : images et instructions par seconde pour chaque
+// ROM. Avec `--features profilage`, le temps est aussi réparti entre le CPU, le PPU et la MMU.
+use std::fs;
+use std::time::Instant;
+
+use emulateur_gameboy::Gameboy;
+
+// Nombre d'images émulées pour chaque ROM.
+const FRAMES: u32 = 1200;
+
+const ROMS: [&str; 2] = ["roms/tetris.gb", "roms/pokemon_blue.gb"];
+
+#[cfg(feature = "profilage")]
+fn pourcentage(duree: std::time::Duration, total: std::time::Duration) -> f64 {
+    100.0 * duree.as_secs_f64() / total.as_secs_f64()
+}
+
+fn mesurer(chemin: &str) {
+    let rom = fs::read(chemin).unwrap_or_else(|_| panic!("{} introuvable", chemin));
+    let mut gameboy = Gameboy::new(rom);
+    gameboy.set_vitesse_limitee(false);
+    let debut = Instant::now();
+    let mut frames = 0;
+    let mut instructions: u64 = 0;
+    while frames < FRAMES {
+        // En HALT, un step avance jusqu'à la prochaine interruption sans exécuter d'instruction.
+        if !gameboy.is_halted() {
+            instructions += 1;
+        }
+        gameboy.step();
+        if gameboy.has_screen_updated() {
+            frames += 1;
+        }
+    }
+    let total = debut.elapsed();
+    let duree = total.as_secs_f64();
+    println!(
+        "{:<22} {} images en {:.2} s, {:.0} images/s, {:.2} M instructions/s",
+        chemin,
+        frames,
+        duree,
+        f64::from(frames) / duree,
+        instructions as f64 / duree / 1e6
+    );
+    #[cfg(feature = "profilage")]
+    {
+        let profil = gameboy.get_profil();
+        let cpu = total.saturating_sub(profil.ppu + profil.mmu);
+        println!(
+            "{:<22} cpu {:.1} %, ppu {:.1} %, mmu {:.1} % ({} événements, {} accès mémoire)",
+            "",
+            pourcentage(cpu, total),
+            pourcentage(profil.ppu, total),
+            pourcentage(profil.mmu, total),
+            profil.evenements,
+            profil.acces
+        );
+    }
+}
+
+fn main() {
+    for chemin in ROMS {
+        mesurer(chemin);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::process;
+
+use argparse::{ArgumentParser, Store, StoreTrue};
+use emulateur_gameboy::regression::{self, Empreintes};
+use emulateur_gameboy::{Gameboy, Rendu};
+
+fn lire(chemin: &str) -> String {
+    fs::read_to_string(chemin).unwrap_or_else(|erreur| {
+        eprintln!("regression: {} : {}", chemin, erreur);
+        process::exit(2);
+    })
+}
+
+fn formater(empreintes: Option<&Empreintes>) -> String {
+    match empreintes {
+        Some(empreintes) => format!("écran {:016x} état {:016x}", empreintes.ecran, empreintes.etat),
+        None => "absente".to_string(),
+    }
+}
+
+fn main() {
+    let mut rom_path = String::from("");
+    let mut reference_path = String::from("");
+    let mut script_path = String::from("");
+    let mut trames: u32 = 600;
+    let mut enregistrer = false;
+    let mut fifo = false;
+    {
+        let mut arg_parser = ArgumentParser::new();
+        arg_parser.set_description("Compare les empreintes de chaque trame à un fichier de référence");
+        arg_parser
+            .refer(&mut rom_path)
+            .add_argument("rom", Store, "Chemin de la ROM")
+            .required();
+        arg_parser
+            .refer(&mut reference_path)
+            .add_argument("reference", Store, "Fichier des empreintes de référence")
+            .required();
+        arg_parser
+            .refer(&mut script_path)
+            .add_option(&["--script"], Store, "Appuis de touches : <trame> appui|relache <bouton> par ligne");
+        arg_parser
+            .refer(&mut trames)
+            .add_option(&["--trames"], Store, "Nombre de trames exécutées (600 par défaut)");
+        arg_parser
+            .refer(&mut fifo)
+            .add_option(&["--fifo"], StoreTrue, "Rendu au dot près (pixel FIFO)");
+        arg_parser
+            .refer(&mut enregistrer)
+            .add_option(&["--enregistrer"], StoreTrue, "Écrit le fichier de référence au lieu de le comparer");
+        arg_parser.parse_args_or_exit();
+    }
+
+    let rom = fs::read(&rom_path).unwrap_or_else(|erreur| {
+        eprintln!("regression: {} : {}", rom_path, erreur);
+        process::exit(2);
+    });
+    let script = if script_path.is_empty() {
+        Vec::new()
+    } else {
+        regression::parse_script(&lire(&script_path)).unwrap_or_else(|erreur| {
+            eprintln!("regression: {}", erreur);
+            process::exit(2);
+        })
+    };
+
+    let mut gameboy = Gameboy::new(rom);
+    if fifo {
+        gameboy.set_rendu(Rendu::Fifo);
+    }
+    let obtenues = regression::executer(&mut gameboy, &script, trames);
+
+    if enregistrer {
+        if let Err(erreur) = fs::write(&reference_path, regression::formater_reference(&obtenues)) {
+            eprintln!("regression: {} : {}", reference_path, erreur);
+            process::exit(2);
+        }
+        println!("{} trames enregistrées dans {}", obtenues.len(), reference_path);
+        return;
+    }
+
+    let attendues = regression::parse_reference(&lire(&reference_path)).unwrap_or_else(|erreur| {
+        eprintln!("regression: {}", erreur);
+        process::exit(2);
+    });
+    match regression::comparer(&obtenues, &attendues) {
+        None => println!("{} trames identiques à la référence", obtenues.len()),
+        Some(trame) => {
+            println!("divergence à la trame {}", trame);
+            println!("  obtenue  : {}", formater(obtenues.get(trame)));
+            println!("  attendue : {}", formater(attendues.get(trame)));
+            process::exit(1);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::cartouches::Cartouche;
+use crate::empreinte::Empreinte;
 use crate::memoire::Memoire;
 
 pub struct MemoireBankController {
@@ -28,13 +30,9 @@ impl Memoire for MemoireBankController {
                 let index = self.rom_bank * 0x4000 + addr as usize - 0x4000;
                 self.rom[index]
             }
-            0xA000..=0xBFFF => {
-                if self.ram_enable {
-                    let index = self.ram_bank * 0x2000 + addr as usize - 0xA000;
-                    self.ram[index]
-                } else {
-                    0x00
-                }
+            0xA000..=0xBFFF if self.ram_enable => {
+                let index = self.ram_bank * 0x2000 + addr as usize - 0xA000;
+                self.ram[index]
             }
             _ => 0x00,
         }
@@ -42,11 +40,9 @@ impl Memoire for MemoireBankController {
 
     fn set_octet(&mut self, addr: u16, value: u8) {
         match addr {
-            0xA000..=0xBFFF => {
-                if self.ram_enable {
-                    let index = self.ram_bank * 0x2000 + addr as usize - 0xA000;
-                    self.ram[index] = value;
-                }
+            0xA000..=0xBFFF if self.ram_enable => {
+                let index = self.ram_bank * 0x2000 + addr as usize - 0xA000;
+                self.ram[index] = value;
             }
             0x0000..=0x1FFF => {
                 self.ram_enable = value & 0x0F == 0x0A;
@@ -61,3 +57,24 @@ impl Memoire for MemoireBankController {
         }
     }
 }
+
+impl Cartouche for MemoireBankController {
+    fn rom_bank(&self) -> usize {
+        self.rom_bank
+    }
+
+    fn ram_bank(&self) -> usize {
+        self.ram_bank
+    }
+
+    fn is_ram_accessible(&self) -> bool {
+        self.ram_enable && !self.ram.is_empty()
+    }
+
+    fn empreinte(&self, empreinte: &mut Empreinte) {
+        empreinte.ecrire_u64(self.rom_bank as u64);
+        empreinte.ecrire_u64(self.ram_bank as u64);
+        empreinte.ecrire_bool(self.ram_enable);
+        empreinte.ecrire(&self.ram);
+    }
+}
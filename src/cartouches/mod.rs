@@ -3,11 +3,33 @@ mod rom;
 
 use crate::cartouches::mbc::MemoireBankController;
 use crate::cartouches::rom::RomOnly;
+use crate::empreinte::Empreinte;
 use crate::memoire::Memoire;
 
-pub trait Cartouche: Memoire + Send {}
+pub trait Cartouche: Memoire + Send {
+    // Banque ROM projetée sur 0x4000-0x7FFF.
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
+    // Banque RAM projetée sur 0xA000-0xBFFF.
+    fn ram_bank(&self) -> usize {
+        0
+    }
+
+    // Cartouche sans MBC : les écritures en ROM n'ont aucun effet.
+    fn is_rom_only(&self) -> bool {
+        false
+    }
+
+    // RAM de la cartouche présente et activée.
+    fn is_ram_accessible(&self) -> bool {
+        false
+    }
 
-impl Cartouche for MemoireBankController {}
+    // État modifiable de la cartouche : registres du MBC et RAM.
+    fn empreinte(&self, _empreinte: &mut Empreinte) {}
+}
 
 pub fn new(rom: Vec<u8>) -> Box<dyn Cartouche> {
     let cartouche: Box<dyn Cartouche> = match rom[0x0147] {
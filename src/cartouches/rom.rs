@@ -13,10 +13,15 @@ impl RomOnly {
 
 impl Memoire for RomOnly {
     fn get_octet(&self, addr: u16) -> u8 {
-        self.rom[addr as usize]
+        // Pas de RAM sur la cartouche : le bus renvoie 0xFF.
+        self.rom.get(addr as usize).copied().unwrap_or(0xFF)
     }
 
     fn set_octet(&mut self, _: u16, _: u8) {}
 }
 
-impl Cartouche for RomOnly {}
\ No newline at end of file
+impl Cartouche for RomOnly {
+    fn is_rom_only(&self) -> bool {
+        true
+    }
+}
\ No newline at end of file
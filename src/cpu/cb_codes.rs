@@ -1,4 +1,5 @@
 use crate::cpu::Cpu;
+use crate::memoire::Bus;
 
 const CB_CYCLES: [u32; 256] = [
     2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2,
@@ -19,7 +20,7 @@ const CB_CYCLES: [u32; 256] = [
     2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
 ];
 
-impl Cpu {
+impl<B: Bus> Cpu<B> {
     pub fn execute_cb(&mut self, cb_code: u8) -> u32 {
         match cb_code {
             0x00 => self.registres.b = self.inst_alu_rlc(self.registres.b),
@@ -1,8 +1,9 @@
 use crate::cpu::registres::CpuFlag;
 use crate::cpu::Cpu;
+use crate::memoire::Bus;
 
 // Instructions CPU
-impl Cpu {
+impl<B: Bus> Cpu<B> {
     // Incrémente la valeur du registre.
     pub fn inst_alu_inc(&mut self, value: u8) -> u8 {
         let result = value.wrapping_add(1);
@@ -292,7 +293,7 @@ impl Cpu {
         self.registres.set_flag_zero(CpuFlag::SUB, false);
         self.registres.set_flag_zero(CpuFlag::HALF_CARRY, false);
         self.registres.set_flag_zero(CpuFlag::CARRY, false);
-        (value >> 4) | (value << 4)
+        value.rotate_left(4)
     }
 
     // Test du bit dans la valeur du registre.
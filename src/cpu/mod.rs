@@ -1,138 +1,376 @@
-mod registres;
+pub mod registres;
 
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::thread;
 use std::time;
 
-use crate::cpu::registres::Registers;
-use crate::memoire::Memoire;
+use crate::cpu::registres::{CpuFlag, Registers};
+use crate::empreinte::Empreinte;
+use crate::memoire::Bus;
 
-pub struct Cpu {
+// Le CPU possède le bus : les accès mémoire sont des appels statiques, sans emprunt à l'exécution.
+pub struct Cpu<B: Bus> {
     pub registres: Registers,
-    pub memoire: Rc<RefCell<dyn Memoire>>,
+    pub memoire: B,
     pub halted: bool,
+    // Mode basse consommation de STOP, dont seul le joypad fait sortir.
     pub stopped: bool,
+    // IME (interrupt master enable).
     pub ei: bool,
+    // EI n'active IME qu'après l'instruction qui le suit.
+    pub ei_en_attente: bool,
+    // HALT bug : le prochain op code est lu sans incrémenter PC.
+    pub halt_bug: bool,
+    // Op code invalide ayant bloqué le CPU, et son adresse.
+    pub verrou: Option<(u16, u8)>,
+    // `LD B, B` sert de breakpoint logiciel aux développeurs homebrew.
+    pub breakpoints_logiciels: bool,
+    pub breakpoint_atteint: bool,
+    // `LD D, D` suivi d'un message au format no$gmb l'affiche sur la sortie d'erreur.
+    pub messages_debug: bool,
+    // Cycles déjà écoulés sur le bus pendant l'instruction en cours.
+    cycles_instruction: u32,
 }
 
-impl Cpu {
-    pub fn new(memoire: Rc<RefCell<dyn Memoire>>) -> Cpu {
+impl<B: Bus> Cpu<B> {
+    pub fn new(memoire: B) -> Cpu<B> {
         Cpu {
             registres: Registers::new(),
             memoire,
             halted: false,
             stopped: false,
             ei: false,
+            ei_en_attente: false,
+            halt_bug: false,
+            verrou: None,
+            breakpoints_logiciels: false,
+            breakpoint_atteint: false,
+            messages_debug: false,
+            cycles_instruction: 0,
         }
     }
 
+    // État du CPU hors bus : registres, IME et modes de basse consommation.
+    pub fn empreinte(&self, empreinte: &mut Empreinte) {
+        let registres = &self.registres;
+        empreinte.ecrire(&[
+            registres.a,
+            registres.b,
+            registres.c,
+            registres.d,
+            registres.e,
+            registres.h,
+            registres.l,
+            registres.flags,
+        ]);
+        empreinte.ecrire_u16(registres.pc);
+        empreinte.ecrire_u16(registres.sp);
+        empreinte.ecrire_bool(self.halted);
+        empreinte.ecrire_bool(self.stopped);
+        empreinte.ecrire_bool(self.ei);
+        empreinte.ecrire_bool(self.ei_en_attente);
+        empreinte.ecrire_bool(self.halt_bug);
+        empreinte.ecrire_bool(self.verrou.is_some());
+    }
+
+    // Fait avancer le reste du système. Chaque accès mémoire du CPU dure un cycle machine
+    // (4 cycles d'horloge), l'accès ayant lieu à la fin du cycle.
+    pub fn tick(&mut self, cycles: u32) {
+        self.memoire.tick(cycles);
+        self.cycles_instruction += cycles;
+    }
+
+    // Cycle machine interne, sans accès mémoire.
+    pub fn tick_interne(&mut self) {
+        self.tick(4);
+    }
+
+    // Lit la mémoire sans consommer de cycle ni déclencher de hook, pour l'état interne du CPU.
+    pub fn lire_octet(&self, addr: u16) -> u8 {
+        self.memoire.lire_interne(addr)
+    }
+
+    fn lire_mot(&self, addr: u16) -> u16 {
+        u16::from(self.lire_octet(addr)) | (u16::from(self.lire_octet(addr.wrapping_add(1))) << 8)
+    }
+
     // Obtient le prochain octet et augmente pc
     pub fn get_octet_at_pc(&mut self) -> u8 {
-        let value = self.memoire.borrow().get_octet(self.registres.pc);
-        self.registres.pc += 1;
+        let value = self.get_octet_in_memoire(self.registres.pc);
+        self.registres.pc = self.registres.pc.wrapping_add(1);
         value
     }
 
     // Obtient le prochain mot et augmente pc
     pub fn get_mot_at_pc(&mut self) -> u16 {
-        let value = self.memoire.borrow().get_mot(self.registres.pc);
-        self.registres.pc += 2;
-        value
+        let low = self.get_octet_at_pc();
+        let high = self.get_octet_at_pc();
+        u16::from(low) | (u16::from(high) << 8)
     }
 
-    pub fn get_octet_in_memoire(&self, addr: u16) -> u8 {
-        self.memoire.borrow().get_octet(addr)
+    pub fn get_octet_in_memoire(&mut self, addr: u16) -> u8 {
+        self.tick(4);
+        self.memoire.get_octet(addr)
     }
 
-    pub fn get_mot_in_memoire(&self, addr: u16) -> u16 {
-        self.memoire.borrow().get_mot(addr)
+    pub fn get_mot_in_memoire(&mut self, addr: u16) -> u16 {
+        let low = self.get_octet_in_memoire(addr);
+        let high = self.get_octet_in_memoire(addr.wrapping_add(1));
+        u16::from(low) | (u16::from(high) << 8)
     }
 
     pub fn set_octet_in_memoire(&mut self, addr: u16, value: u8) {
-        self.memoire.borrow_mut().set_octet(addr, value);
+        self.tick(4);
+        self.memoire.set_octet(addr, value);
     }
 
     pub fn set_mot_in_memoire(&mut self, addr: u16, value: u16) {
-        self.memoire.borrow_mut().set_mot(addr, value);
+        self.set_octet_in_memoire(addr, (value & 0xFF) as u8);
+        self.set_octet_in_memoire(addr.wrapping_add(1), (value >> 8) as u8);
     }
 
+    // Un cycle interne précède l'écriture, l'octet de poids fort est empilé en premier.
     pub fn add_to_stack(&mut self, value: u16) {
-        self.registres.sp -= 2;
-        self.set_mot_in_memoire(self.registres.sp, value);
+        self.tick_interne();
+        self.registres.sp = self.registres.sp.wrapping_sub(1);
+        self.set_octet_in_memoire(self.registres.sp, (value >> 8) as u8);
+        self.registres.sp = self.registres.sp.wrapping_sub(1);
+        self.set_octet_in_memoire(self.registres.sp, (value & 0xFF) as u8);
+    }
+
+    // INC/DEC 16 bits : le registre passe sur le bus d'adresses pendant le cycle interne.
+    fn placer_adresse(&mut self, addr: u16) {
+        self.memoire.placer_adresse(addr);
     }
 
     pub fn pop_stack(&mut self) -> u16 {
         let result = self.get_mot_in_memoire(self.registres.sp);
-        self.registres.sp += 2;
+        self.registres.sp = self.registres.sp.wrapping_add(2);
         result
     }
 
-    // Le flag IME (interrupt master enable) est réinitialisé et interdit toutes les interruptions.
+    // Message de debug au format no$gmb, placé juste après `LD D, D` :
+    //  jr .fin
+    //  dw $6464
+    //  dw $0000
+    //  db "message"
+    // .fin
+    pub fn lire_message_debug(&self) -> Option<String> {
+        let pc = self.registres.pc;
+        if self.lire_octet(pc) != 0x18 {
+            return None;
+        }
+        let longueur = self.lire_octet(pc.wrapping_add(1));
+        if longueur < 4
+            || self.lire_mot(pc.wrapping_add(2)) != 0x6464
+            || self.lire_mot(pc.wrapping_add(4)) != 0x0000
+        {
+            return None;
+        }
+        let message: Vec<u8> = (0..u16::from(longueur) - 4)
+            .map(|i| self.lire_octet(pc.wrapping_add(6 + i)))
+            .collect();
+        Some(self.developper_message(&String::from_utf8_lossy(&message)))
+    }
+
+    // Remplace les %reg% du message par la valeur du registre.
+    fn developper_message(&self, message: &str) -> String {
+        let mut resultat = String::new();
+        let mut reste = message;
+        while let Some(debut) = reste.find('%') {
+            resultat += &reste[..debut];
+            let suite = &reste[debut + 1..];
+            match suite.find('%') {
+                Some(fin) => match self.valeur_registre(&suite[..fin]) {
+                    Some(valeur) => {
+                        resultat += &valeur;
+                        reste = &suite[fin + 1..];
+                    }
+                    None => {
+                        resultat.push('%');
+                        reste = suite;
+                    }
+                },
+                None => {
+                    resultat.push('%');
+                    reste = suite;
+                }
+            }
+        }
+        resultat + reste
+    }
+
+    fn valeur_registre(&self, nom: &str) -> Option<String> {
+        let registres = &self.registres;
+        let flag = |flag: CpuFlag| Some(u8::from(registres.has_flag(flag)).to_string());
+        match nom.to_ascii_uppercase().as_str() {
+            "A" => Some(format!("{:02X}", registres.a)),
+            "F" => Some(format!("{:02X}", registres.flags & 0xF0)),
+            "B" => Some(format!("{:02X}", registres.b)),
+            "C" => Some(format!("{:02X}", registres.c)),
+            "D" => Some(format!("{:02X}", registres.d)),
+            "E" => Some(format!("{:02X}", registres.e)),
+            "H" => Some(format!("{:02X}", registres.h)),
+            "L" => Some(format!("{:02X}", registres.l)),
+            "AF" => Some(format!("{:04X}", registres.af())),
+            "BC" => Some(format!("{:04X}", registres.bc())),
+            "DE" => Some(format!("{:04X}", registres.de())),
+            "HL" => Some(format!("{:04X}", registres.hl())),
+            "SP" => Some(format!("{:04X}", registres.sp)),
+            // PC de l'instruction `LD D, D`
+            "PC" => Some(format!("{:04X}", registres.pc.wrapping_sub(1))),
+            "ZF" => flag(CpuFlag::ZERO),
+            "NF" => flag(CpuFlag::SUB),
+            "HF" => flag(CpuFlag::HALF_CARRY),
+            "CF" => flag(CpuFlag::CARRY),
+            _ => None,
+        }
+    }
+
+    // Les op codes invalides bloquent le CPU jusqu'à la mise hors tension, même les
+    // interruptions ne le réveillent plus.
+    pub fn verrouiller(&mut self, op_code: u8) {
+        self.verrou = Some((self.registres.pc.wrapping_sub(1), op_code));
+    }
+
+    // HALT attend une interruption. Avec IME à 0 et une interruption déjà en attente, le CPU ne
+    // s'arrête pas et l'octet suivant est lu deux fois (HALT bug). Si EI précède HALT, l'interruption
+    // est servie avec HALT comme adresse de retour et HALT est de nouveau exécuté au retour.
+    pub fn inst_halt(&mut self) {
+        if self.ei || self.get_interruptions() == 0x00 {
+            self.halted = true;
+        } else if self.ei_en_attente {
+            self.registres.pc = self.registres.pc.wrapping_sub(1);
+        } else {
+            self.halt_bug = true;
+        }
+    }
+
+    // IF et IE sont lus par le chemin interne : les hooks ne voient que les accès des instructions.
+    fn get_interruptions(&self) -> u8 {
+        self.lire_octet(0xFF0F) & self.lire_octet(0xFFFF) & 0x1F
+    }
+
+    // Une interruption demandée (IF) et autorisée (IE) sort le CPU de HALT. Si IME est actif, elle est
+    // servie en 5 cycles machine : 2 cycles d'attente, PC est empilé, puis PC saute au vecteur.
+    // IE et IF sont relus après l'écriture de l'octet de poids fort de PC : si cette écriture touche IE
+    // et annule l'interruption, le CPU saute en 0x0000.
     pub fn gerer_interruptions(&mut self) -> u32 {
-        if !self.halted && !self.ei {
+        if self.get_interruptions() == 0x00 {
             return 0;
         }
-        let interruptions_asserted = self.get_octet_in_memoire(0xFF0F);
-        let interruptions_enabled = self.get_octet_in_memoire(0xFFFF);
-        let interruptions = interruptions_asserted & interruptions_enabled;
-        if interruptions == 0x00 {
-            return 0;
+        self.cycles_instruction = 0;
+        // La sortie de HALT coûte un cycle machine supplémentaire.
+        if self.halted {
+            self.halted = false;
+            self.tick_interne();
         }
-        self.halted = false;
         if !self.ei {
-            return 0;
+            return self.cycles_instruction;
         }
         self.ei = false;
-        // Consomme une interruption et écrit le reste en mémoire
-        let n = interruptions.trailing_zeros();
-        let interruptions_asserted = interruptions_asserted & !(1 << n);
-        self.set_octet_in_memoire(0xFF0F, interruptions_asserted);
-        self.add_to_stack(self.registres.pc);
-        // Régle le PC pour qu'il corresponde au programme d'interruption du process
-        self.registres.pc = 0x0040 | ((n as u16) << 3);
-        4
+        self.tick_interne();
+        self.tick_interne();
+        self.registres.sp = self.registres.sp.wrapping_sub(1);
+        self.set_octet_in_memoire(self.registres.sp, (self.registres.pc >> 8) as u8);
+        let interruptions = self.get_interruptions();
+        self.registres.sp = self.registres.sp.wrapping_sub(1);
+        self.set_octet_in_memoire(self.registres.sp, (self.registres.pc & 0xFF) as u8);
+        self.registres.pc = if interruptions == 0x00 {
+            0x0000
+        } else {
+            // Consomme l'interruption la plus prioritaire
+            let n = interruptions.trailing_zeros();
+            let interruptions_asserted = self.lire_octet(0xFF0F) & !(1 << n);
+            self.memoire.ecrire_interne(0xFF0F, interruptions_asserted);
+            // Régle le PC pour qu'il corresponde au programme d'interruption du process
+            0x0040 | ((n as u16) << 3)
+        };
+        self.tick_interne();
+        self.cycles_instruction
     }
 
+    // Exécute une instruction, ou attend une interruption en HALT, et renvoie sa durée en cycles d'horloge.
+    // Le reste du système avance au fil des accès mémoire de l'instruction.
     pub fn run(&mut self) -> u32 {
-        let cycles = {
-            match self.gerer_interruptions() {
-                0 => {}
-                n => return n,
-            }
-            if self.halted {
-                1
-            } else {
-                let op_code = self.get_octet_at_pc();
-                self.execute(op_code)
-            }
-        };
-        cycles * 4
+        // En STOP, le système est à l'arrêt. Bloqué, le CPU n'exécute plus rien mais le reste du
+        // système continue d'avancer.
+        if self.stopped {
+            return 4;
+        }
+        if self.verrou.is_some() {
+            self.tick(4);
+            return 4;
+        }
+        match self.gerer_interruptions() {
+            0 => {}
+            n => return n,
+        }
+        self.cycles_instruction = 0;
+        if self.halted {
+            // Seul un événement du système peut réveiller le CPU : il avance directement jusqu'à la
+            // prochaine interruption, sans dépasser une période de STEP_CYCLES pour que l'affichage
+            // et les entrées continuent d'être traités.
+            let cycles = self.memoire.attendre_interruption(STEP_CYCLES);
+            self.cycles_instruction += cycles;
+            return cycles;
+        }
+        self.executer_instruction()
+    }
+
+    // Lit l'op code à PC et exécute l'instruction, cycles internes compris.
+    fn executer_instruction(&mut self) -> u32 {
+        self.cycles_instruction = 0;
+        let ei_en_attente = self.ei_en_attente;
+        self.tick(4);
+        let op_code = self.memoire.get_op_code(self.registres.pc);
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registres.pc = self.registres.pc.wrapping_add(1);
+        }
+        let cycles = self.execute(op_code) * 4;
+        // Cycles internes restants de l'instruction, après son dernier accès mémoire.
+        if cycles > self.cycles_instruction {
+            self.tick(cycles - self.cycles_instruction);
+        }
+        // IME est activé une fois l'instruction qui suit EI exécutée, sauf si DI l'a annulé entre temps.
+        if ei_en_attente && self.ei_en_attente {
+            self.ei = true;
+            self.ei_en_attente = false;
+        }
+        self.cycles_instruction
     }
 }
 
 mod cb_codes;
 mod instructions;
 mod op_codes;
+#[cfg(test)]
+mod tests;
 
 pub const CLOCK_FREQUENCY: u32 = 4_194_304;
+// Durée de la pause du CPU après un changement de vitesse (2050 cycles machine).
+pub const VITESSE_SWITCH_CYCLES: u32 = 2050 * 4;
 pub const STEP_TIME: u32 = 16;
 pub const STEP_CYCLES: u32 = (STEP_TIME as f64 / (1000_f64 / CLOCK_FREQUENCY as f64)) as u32;
 
-pub struct RealTimeCpu {
-    pub cpu: Cpu,
+pub struct RealTimeCpu<B: Bus> {
+    pub cpu: Cpu<B>,
     step_cycles: u32,
     step_zero: time::Instant,
     step_flip: bool,
+    // Sans limite, l'émulation tourne aussi vite que possible (benchmarks, tests).
+    pub limite: bool,
 }
 
-impl RealTimeCpu {
-    pub fn new(memoire: Rc<RefCell<dyn Memoire>>) -> RealTimeCpu {
+impl<B: Bus> RealTimeCpu<B> {
+    pub fn new(memoire: B) -> RealTimeCpu<B> {
         RealTimeCpu {
             cpu: Cpu::new(memoire),
             step_cycles: 0,
             step_zero: time::Instant::now(),
             step_flip: false,
+            limite: true,
         }
     }
 
@@ -142,17 +380,8 @@ impl RealTimeCpu {
         if self.step_cycles > STEP_CYCLES {
             self.step_flip = true;
             self.step_cycles -= STEP_CYCLES;
-            let now = time::Instant::now();
-            let duration = now.duration_since(self.step_zero);
-            let s = u64::from(STEP_TIME.saturating_sub(duration.as_millis() as u32));
-            thread::sleep(time::Duration::from_millis(s));
-            self.step_zero = self
-                .step_zero
-                .checked_add(time::Duration::from_millis(u64::from(STEP_TIME)))
-                .unwrap();
-
-            if now.checked_duration_since(self.step_zero).is_some() {
-                self.step_zero = now;
+            if self.limite {
+                self.attendre();
             }
         }
         let cycles = self.cpu.run();
@@ -160,6 +389,21 @@ impl RealTimeCpu {
         cycles
     }
 
+    fn attendre(&mut self) {
+        let now = time::Instant::now();
+        let duration = now.duration_since(self.step_zero);
+        let s = u64::from(STEP_TIME.saturating_sub(duration.as_millis() as u32));
+        thread::sleep(time::Duration::from_millis(s));
+        self.step_zero = self
+            .step_zero
+            .checked_add(time::Duration::from_millis(u64::from(STEP_TIME)))
+            .unwrap();
+
+        if now.checked_duration_since(self.step_zero).is_some() {
+            self.step_zero = now;
+        }
+    }
+
     pub fn flip(&mut self) -> bool {
         let step_flip = self.step_flip;
         if step_flip {
@@ -1,32 +1,34 @@
 use crate::cpu::registres::CpuFlag;
 use crate::cpu::Cpu;
+use crate::memoire::Bus;
 
 // Les documents Nintendo décrivent la vitesse du CPU et des instructions en cycles de machine,
 // ce document les décrit en cycles d'horloge. 1 cycle machine = 4 cycles d'horloge
+// Les op codes invalides ne coûtent que leur lecture avant de bloquer le CPU.
 
 //  0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f
 const OP_CYCLES: [u32; 256] = [
     1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0
-    0, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1
+    2, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1
     2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2
     2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6
-    2, 2, 2, 2, 2, 2, 0, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // a
     1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // b
     2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // c
-    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // d
-    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // e
-    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, // f
+    2, 3, 3, 1, 3, 4, 2, 4, 2, 4, 3, 1, 3, 1, 2, 4, // d
+    3, 3, 2, 1, 1, 4, 2, 4, 4, 1, 4, 1, 1, 1, 2, 4, // e
+    3, 3, 2, 1, 1, 4, 2, 4, 3, 2, 4, 1, 1, 1, 2, 4, // f
 ];
 
 
 // Mappage du code OP de l'UC
-impl Cpu {
+impl<B: Bus> Cpu<B> {
      pub fn execute(&mut self, op_code: u8) -> u32 {
         match op_code {
             // NOP
@@ -42,6 +44,7 @@ impl Cpu {
             }
             // INC BC
             0x03 => {
+                self.placer_adresse(self.registres.bc());
                 let value = self.registres.bc().wrapping_add(1);
                 self.registres.set_bc(value);
             }
@@ -68,6 +71,7 @@ impl Cpu {
             0x0A => self.registres.a = self.get_octet_in_memoire(self.registres.bc()),
             // DEC BC
             0x0B => {
+                self.placer_adresse(self.registres.bc());
                 let value = self.registres.bc().wrapping_sub(1);
                 self.registres.set_bc(value)
             }
@@ -84,7 +88,11 @@ impl Cpu {
                 self.registres.set_flag_zero(CpuFlag::ZERO, false);
             }
             // STOP
-            0x10 => self.stopped = true,
+            0x10 => {
+                // STOP est encodé sur deux octets, le second est ignoré.
+                self.get_octet_at_pc();
+                self.stopped = true;
+            }
             // LD DE, d16
             0x11 => {
                 let value = self.get_mot_at_pc();
@@ -94,6 +102,7 @@ impl Cpu {
             0x12 => self.set_octet_in_memoire(self.registres.de(), self.registres.a),
             // INC DE
             0x13 => {
+                self.placer_adresse(self.registres.de());
                 let value = self.registres.de().wrapping_add(1);
                 self.registres.set_de(value);
             }
@@ -120,6 +129,7 @@ impl Cpu {
             0x1A => self.registres.a = self.get_octet_in_memoire(self.registres.de()),
             // DEC DE
             0x1B => {
+                self.placer_adresse(self.registres.de());
                 let value = self.registres.de().wrapping_sub(1);
                 self.registres.set_de(value);
             }
@@ -155,6 +165,7 @@ impl Cpu {
             }
             // INC HL
             0x23 => {
+                self.placer_adresse(self.registres.hl());
                 let value = self.registres.hl().wrapping_add(1);
                 self.registres.set_hl(value);
             }
@@ -183,6 +194,7 @@ impl Cpu {
             }
             // DEC HL
             0x2B => {
+                self.placer_adresse(self.registres.hl());
                 let value = self.registres.hl().wrapping_sub(1);
                 self.registres.set_hl(value);
             }
@@ -211,6 +223,7 @@ impl Cpu {
             }
             // INC SP
             0x33 => {
+                self.placer_adresse(self.registres.sp);
                 let value = self.registres.sp.wrapping_add(1);
                 self.registres.sp = value;
             }
@@ -253,6 +266,7 @@ impl Cpu {
             }
             // DEC SP
             0x3B => {
+                self.placer_adresse(self.registres.sp);
                 let value = self.registres.sp.wrapping_sub(1);
                 self.registres.sp = value;
             }
@@ -265,7 +279,11 @@ impl Cpu {
             // CCF
             0x3F => self.inst_alu_ccf(),
             // LD B, B
-            0x40 => {}
+            0x40 => {
+                if self.breakpoints_logiciels {
+                    self.breakpoint_atteint = true;
+                }
+            }
             // LB B, C
             0x41 => self.registres.b = self.registres.c,
             // LD B, D
@@ -301,7 +319,13 @@ impl Cpu {
             // LD D, C
             0x51 => self.registres.d = self.registres.c,
             // LD D, D
-            0x52 => {}
+            0x52 => {
+                if self.messages_debug {
+                    if let Some(message) = self.lire_message_debug() {
+                        eprintln!("{}", message);
+                    }
+                }
+            }
             // LD D, E
             0x53 => self.registres.d = self.registres.e,
             // LD D, H
@@ -373,7 +397,7 @@ impl Cpu {
             // LD (HL), L
             0x75 => self.set_octet_in_memoire(self.registres.hl(), self.registres.l),
             // HALT
-            0x76 => self.halted = true,
+            0x76 => self.inst_halt(),
             // LD (HL), A
             0x77 => self.set_octet_in_memoire(self.registres.hl(), self.registres.a),
             // LD A, B
@@ -546,6 +570,8 @@ impl Cpu {
             0xBF => self.inst_alu_cp(self.registres.a),
             // RET NZ
             0xC0 => {
+                // Cycle interne d'évaluation de la condition
+                self.tick_interne();
                 // Not Zero
                 if !self.registres.has_flag(CpuFlag::ZERO) {
                     self.registres.pc = self.pop_stack();
@@ -587,6 +613,8 @@ impl Cpu {
             }
             // RET Z
             0xC8 => {
+                // Cycle interne d'évaluation de la condition
+                self.tick_interne();
                 // Not Zero
                 if self.registres.has_flag(CpuFlag::ZERO) {
                     self.registres.pc = self.pop_stack();
@@ -632,6 +660,8 @@ impl Cpu {
             }
             // RET NC
             0xD0 => {
+                // Cycle interne d'évaluation de la condition
+                self.tick_interne();
                 // Not Carry
                 if !self.registres.has_flag(CpuFlag::CARRY) {
                     self.registres.pc = self.pop_stack();
@@ -650,7 +680,7 @@ impl Cpu {
                 }
             }
             // Not Valid
-            0xD3 => panic!("cpu: invalid op code 0xD3"),
+            0xD3 => self.verrouiller(op_code),
             // CALL NC, a16
             0xD4 => {
                 let n = self.get_mot_at_pc();
@@ -673,6 +703,8 @@ impl Cpu {
             }
             // RET C
             0xD8 => {
+                // Cycle interne d'évaluation de la condition
+                self.tick_interne();
                 // Carry
                 if self.registres.has_flag(CpuFlag::CARRY) {
                     self.registres.pc = self.pop_stack();
@@ -691,7 +723,7 @@ impl Cpu {
                 }
             }
             // Not Valid
-            0xDB => panic!("cpu: invalid op code 0xDB"),
+            0xDB => self.verrouiller(op_code),
             // CALL C, a16
             0xDC => {
                 let n = self.get_mot_at_pc();
@@ -701,7 +733,7 @@ impl Cpu {
                 }
             }
             // Not Valid
-            0xDD => panic!("cpu: invalid op code 0xDD"),
+            0xDD => self.verrouiller(op_code),
             // SBC A, d8
             0xDE => {
                 let value = self.get_octet_at_pc();
@@ -728,9 +760,9 @@ impl Cpu {
                 self.set_octet_in_memoire(addr, self.registres.a);
             }
             // Not Valid
-            0xE3 => panic!("cpu: invalid op code 0xE3"),
+            0xE3 => self.verrouiller(op_code),
             // Not Valid
-            0xE4 => panic!("cpu: invalid op code 0xE4"),
+            0xE4 => self.verrouiller(op_code),
             // PUSH HL
             0xE5 => self.add_to_stack(self.registres.hl()),
             // AND d8
@@ -756,11 +788,11 @@ impl Cpu {
                 self.set_octet_in_memoire(addr, self.registres.a);
             }
             // Not Valid
-            0xEB => panic!("cpu: invalid op code 0xEB"),
+            0xEB => self.verrouiller(op_code),
             // Not Valid
-            0xEC => panic!("cpu: invalid op code 0xEC"),
+            0xEC => self.verrouiller(op_code),
             // Not Valid
-            0xED => panic!("cpu: invalid op code 0xED"),
+            0xED => self.verrouiller(op_code),
             // XOR d8
             0xEE => {
                 let value = self.get_octet_at_pc();
@@ -787,9 +819,12 @@ impl Cpu {
                 self.registres.a = self.get_octet_in_memoire(addr);
             }
             // DI
-            0xF3 => self.ei = false,
+            0xF3 => {
+                self.ei = false;
+                self.ei_en_attente = false;
+            }
             // Not Valid
-            0xF4 => panic!("cpu: invalid op code 0xF4"),
+            0xF4 => self.verrouiller(op_code),
             // PUSH AF
             0xF5 => self.add_to_stack(self.registres.af()),
             // OR d8
@@ -822,11 +857,11 @@ impl Cpu {
                 self.registres.a = self.get_octet_in_memoire(addr);
             }
             // EI
-            0xFB => self.ei = true,
+            0xFB => self.ei_en_attente = true,
             // Not Valid
-            0xFC => panic!("cpu: invalid op code 0xFC"),
+            0xFC => self.verrouiller(op_code),
             // Not Valid
-            0xFD => panic!("cpu: invalid op code 0xFD"),
+            0xFD => self.verrouiller(op_code),
             // CP d8
             0xFE => {
                 let value = self.get_octet_at_pc();
@@ -838,32 +873,19 @@ impl Cpu {
                 self.registres.pc = 0x38;
             }
         };
+        // Cycles supplémentaires des branchements conditionnels pris.
+        // La condition est codée par les bits 3 et 4 : NZ, Z, NC, C.
+        let condition = match op_code & 0x18 {
+            0x00 => !self.registres.has_flag(CpuFlag::ZERO),
+            0x08 => self.registres.has_flag(CpuFlag::ZERO),
+            0x10 => !self.registres.has_flag(CpuFlag::CARRY),
+            _ => self.registres.has_flag(CpuFlag::CARRY),
+        };
         let ecycle = match op_code {
-            0x20 | 0x30 => u32::from(!self.registres.has_flag(CpuFlag::ZERO)),
-            0x28 | 0x38 => u32::from(self.registres.has_flag(CpuFlag::ZERO)),
-            0xC0 | 0xD0 => {
-                if self.registres.has_flag(CpuFlag::ZERO) {
-                    0x00
-                } else {
-                    0x03
-                }
-            }
-            0xC8 | 0xCC | 0xD8 | 0xDC => {
-                if self.registres.has_flag(CpuFlag::ZERO) {
-                    0x03
-                } else {
-                    0x00
-                }
-            }
-            0xC2 | 0xD2 => u32::from(!self.registres.has_flag(CpuFlag::ZERO)),
-            0xCA | 0xDA => u32::from(self.registres.has_flag(CpuFlag::ZERO)),
-            0xC4 | 0xD4 => {
-                if self.registres.has_flag(CpuFlag::ZERO) {
-                    0x00
-                } else {
-                    0x03
-                }
-            }
+            // JR cc, JP cc
+            0x20 | 0x28 | 0x30 | 0x38 | 0xC2 | 0xCA | 0xD2 | 0xDA => u32::from(condition),
+            // RET cc, CALL cc
+            0xC0 | 0xC8 | 0xD0 | 0xD8 | 0xC4 | 0xCC | 0xD4 | 0xDC => u32::from(condition) * 0x03,
             _ => 0x00,
         };
         OP_CYCLES[op_code as usize] + ecycle
@@ -15,6 +15,7 @@ pub struct Registers {
 }
 
 #[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum CpuFlag {
     ZERO = 0b10000000,
     SUB = 0b01000000,
@@ -23,9 +24,15 @@ pub enum CpuFlag {
     CARRY = 0b00010000,
 }
 
+impl Default for Registers {
+    fn default() -> Registers {
+        Registers::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Registers {
-        let registers = Registers {
+        Registers {
             a: 0,
             b: 0,
             c: 0,
@@ -38,28 +45,27 @@ impl Registers {
             sp: 0xFFFE,
 
             flags: 0,
-        };
-        registers
+        }
     }
 
     pub fn get_msb(&self, a: u8, b: u8) -> u16 {
-        return ((a as u16) << 8) | (b as u16);
+        ((a as u16) << 8) | (b as u16)
     }
 
     pub fn af(&self) -> u16 {
-        return self.get_msb(self.a, self.flags & 0xF0);
+        self.get_msb(self.a, self.flags & 0xF0)
     }
 
     pub fn bc(&self) -> u16 {
-        return self.get_msb(self.b, self.c);
+        self.get_msb(self.b, self.c)
     }
 
     pub fn de(&self) -> u16 {
-        return self.get_msb(self.d, self.e);
+        self.get_msb(self.d, self.e)
     }
 
     pub fn hl(&self) -> u16 {
-        return self.get_msb(self.h, self.l);
+        self.get_msb(self.h, self.l)
     }
     pub fn set_af(&mut self, value: u16) {
         self.a = (value >> 8) as u8;
@@ -83,13 +89,13 @@ impl Registers {
 
     pub fn hl_then_inc(&mut self) -> u16 {
         let res = self.hl();
-        self.set_hl(res + 1);
+        self.set_hl(res.wrapping_add(1));
         res
     }
 
     pub fn hl_then_dec(&mut self) -> u16 {
         let res = self.hl();
-        self.set_hl(res - 1);
+        self.set_hl(res.wrapping_sub(1));
         res
     }
 
@@ -0,0 +1,315 @@
+// Vecteurs de test SM83 par op code (https://github.com/SingleStepTests/sm83) : un fichier JSON par
+// op code ("00.json" ... "cb ff.json"), chaque test donnant l'état initial, l'état final et les accès
+// au bus de chaque cycle machine. Les fichiers sont lus dans $SM83_TESTS, ou test-vectors/sm83 à la
+// racine du projet. Le test, ignoré par défaut, échoue s'ils sont absents : cargo test -- --ignored.
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::cpu::Cpu;
+use crate::memoire::{Bus, Memoire};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Acces {
+    addr: u16,
+    value: u8,
+    ecriture: bool,
+}
+
+// Mémoire plate de 64 Kio qui enregistre l'accès effectué à chaque cycle machine.
+struct MemoireTest {
+    ram: Vec<u8>,
+    cycles: RefCell<Vec<Option<Acces>>>,
+}
+
+impl MemoireTest {
+    fn new() -> MemoireTest {
+        MemoireTest {
+            ram: vec![0x00; 0x10000],
+            cycles: RefCell::new(Vec::new()),
+        }
+    }
+
+    // Seul le premier accès d'un cycle passe par le bus.
+    fn enregistrer(&self, acces: Acces) {
+        if let Some(cycle @ None) = self.cycles.borrow_mut().last_mut() {
+            *cycle = Some(acces);
+        }
+    }
+}
+
+impl Memoire for MemoireTest {
+    fn get_octet(&self, addr: u16) -> u8 {
+        let value = self.ram[addr as usize];
+        self.enregistrer(Acces {
+            addr,
+            value,
+            ecriture: false,
+        });
+        value
+    }
+
+    fn set_octet(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+        self.enregistrer(Acces {
+            addr,
+            value,
+            ecriture: true,
+        });
+    }
+}
+
+impl Bus for MemoireTest {
+    fn tick(&mut self, cycles: u32) {
+        let cycles_machine = cycles as usize / 4;
+        self.cycles
+            .get_mut()
+            .extend(std::iter::repeat_n(None, cycles_machine));
+    }
+
+    // Les accès internes du CPU (IE et IF pour HALT et les interruptions) ne sont pas des accès au bus.
+    fn lire_interne(&self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn ecrire_interne(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+    }
+}
+
+fn get_u16(etat: &Value, nom: &str) -> u16 {
+    etat[nom]
+        .as_u64()
+        .unwrap_or_else(|| panic!("sm83: champ {} manquant", nom)) as u16
+}
+
+fn charger_etat(cpu: &mut Cpu<MemoireTest>, etat: &Value) {
+    let registres = &mut cpu.registres;
+    registres.a = get_u16(etat, "a") as u8;
+    registres.b = get_u16(etat, "b") as u8;
+    registres.c = get_u16(etat, "c") as u8;
+    registres.d = get_u16(etat, "d") as u8;
+    registres.e = get_u16(etat, "e") as u8;
+    registres.flags = get_u16(etat, "f") as u8;
+    registres.h = get_u16(etat, "h") as u8;
+    registres.l = get_u16(etat, "l") as u8;
+    registres.pc = get_u16(etat, "pc");
+    registres.sp = get_u16(etat, "sp");
+    cpu.ei = etat["ime"].as_u64() == Some(1);
+    cpu.ei_en_attente = etat["ei"].as_u64() == Some(1);
+    if let Some(ie) = etat["ie"].as_u64() {
+        cpu.memoire.ram[0xFFFF] = ie as u8;
+    }
+    for case in etat["ram"].as_array().into_iter().flatten() {
+        cpu.memoire.ram[case[0].as_u64().unwrap() as usize] = case[1].as_u64().unwrap() as u8;
+    }
+}
+
+fn comparer_etat(cpu: &Cpu<MemoireTest>, etat: &Value) -> Vec<String> {
+    let registres = &cpu.registres;
+    let mut differences = Vec::new();
+    let mut comparer = |nom: &str, valeur: u16| {
+        if let Some(attendue) = etat[nom].as_u64() {
+            if attendue != u64::from(valeur) {
+                differences.push(format!("{}={:X} attendu {:X}", nom, valeur, attendue));
+            }
+        }
+    };
+    comparer("a", u16::from(registres.a));
+    comparer("b", u16::from(registres.b));
+    comparer("c", u16::from(registres.c));
+    comparer("d", u16::from(registres.d));
+    comparer("e", u16::from(registres.e));
+    comparer("f", u16::from(registres.flags));
+    comparer("h", u16::from(registres.h));
+    comparer("l", u16::from(registres.l));
+    comparer("pc", registres.pc);
+    comparer("sp", registres.sp);
+    comparer("ime", u16::from(cpu.ei));
+    comparer("ei", u16::from(cpu.ei_en_attente));
+    comparer("ie", u16::from(cpu.memoire.ram[0xFFFF]));
+    for case in etat["ram"].as_array().into_iter().flatten() {
+        let addr = case[0].as_u64().unwrap() as u16;
+        let attendue = case[1].as_u64().unwrap() as u8;
+        let valeur = cpu.memoire.ram[addr as usize];
+        if valeur != attendue {
+            differences.push(format!("[{:04X}]={:02X} attendu {:02X}", addr, valeur, attendue));
+        }
+    }
+    differences
+}
+
+// Cycles attendus : [adresse, valeur, "r-m" | "-wm" | "---"], les cycles internes n'accédant pas au bus.
+fn comparer_cycles(cycles: &[Option<Acces>], attendus: &[Value]) -> Option<String> {
+    let attendus: Vec<Option<Acces>> = attendus
+        .iter()
+        .map(|cycle| {
+            let nature = cycle[2].as_str().unwrap_or("---");
+            if !nature.contains('r') && !nature.contains('w') {
+                return None;
+            }
+            Some(Acces {
+                addr: cycle[0].as_u64()? as u16,
+                value: cycle[1].as_u64()? as u8,
+                ecriture: nature.contains('w'),
+            })
+        })
+        .collect();
+    if cycles == attendus.as_slice() {
+        return None;
+    }
+    Some(format!("cycles {:?}\n  attendus {:?}", cycles, attendus))
+}
+
+// Exécute un test et renvoie la description de la première divergence.
+fn executer_test(test: &Value) -> Option<String> {
+    let mut cpu = Cpu::new(MemoireTest::new());
+    charger_etat(&mut cpu, &test["initial"]);
+    cpu.executer_instruction();
+    let differences = comparer_etat(&cpu, &test["final"]);
+    if !differences.is_empty() {
+        return Some(differences.join(" "));
+    }
+    let attendus = test["cycles"].as_array()?;
+    comparer_cycles(cpu.memoire.cycles.get_mut(), attendus)
+}
+
+fn get_repertoire() -> PathBuf {
+    match env::var_os("SM83_TESTS") {
+        Some(repertoire) => PathBuf::from(repertoire),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-vectors/sm83"),
+    }
+}
+
+#[test]
+#[ignore = "vecteurs SM83 externes, voir $SM83_TESTS"]
+fn sm83() {
+    let repertoire = get_repertoire();
+    let entrees = fs::read_dir(&repertoire)
+        .unwrap_or_else(|erreur| panic!("sm83: {} introuvable : {}", repertoire.display(), erreur));
+    let mut fichiers: Vec<PathBuf> = entrees
+        .filter_map(|entree| entree.ok().map(|entree| entree.path()))
+        .filter(|chemin| chemin.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    fichiers.sort();
+
+    let mut echecs = Vec::new();
+    for fichier in &fichiers {
+        let op_code = fichier.file_stem().unwrap().to_string_lossy().to_uppercase();
+        let contenu = fs::read_to_string(fichier).unwrap();
+        let tests: Value = serde_json::from_str(&contenu)
+            .unwrap_or_else(|erreur| panic!("sm83: {} invalide : {}", fichier.display(), erreur));
+        let tests = tests.as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut reussis = 0;
+        let mut premier_echec = None;
+        for test in tests {
+            match executer_test(test) {
+                None => reussis += 1,
+                Some(difference) => {
+                    premier_echec.get_or_insert_with(|| format!("{} : {}", test["name"], difference));
+                }
+            }
+        }
+        let statut = if reussis == tests.len() { "ok" } else { "ÉCHEC" };
+        println!("{:<6} {:>5}/{:<5} {}", op_code, reussis, tests.len(), statut);
+        if let Some(echec) = premier_echec {
+            println!("  {}", echec);
+            echecs.push(op_code);
+        }
+    }
+    assert!(echecs.is_empty(), "sm83: op codes en échec : {}", echecs.join(", "));
+}
+
+#[test]
+fn verrou_fait_avancer_le_systeme() {
+    let mut cpu = Cpu::new(MemoireTest::new());
+    cpu.memoire.ram[0x0100] = 0xD3;
+    cpu.run();
+    assert!(cpu.verrou.is_some());
+    let cycles = cpu.memoire.cycles.borrow().len();
+    assert_eq!(cpu.run(), 4);
+    assert_eq!(cpu.memoire.cycles.borrow().len(), cycles + 1);
+    // En STOP, rien n'avance.
+    cpu.stopped = true;
+    cpu.run();
+    assert_eq!(cpu.memoire.cycles.borrow().len(), cycles + 1);
+}
+
+// La durée renvoyée par run() est celle que le reste du système a vu passer, pour chaque op code.
+#[test]
+fn duree_egale_aux_cycles_du_bus() {
+    for prefixe in [None, Some(0xCB)] {
+        for op_code in 0x00..=0xFF {
+            let mut cpu = Cpu::new(MemoireTest::new());
+            match prefixe {
+                Some(prefixe) => cpu.memoire.ram[0x0100..0x0102].copy_from_slice(&[prefixe, op_code]),
+                None => cpu.memoire.ram[0x0100] = op_code,
+            }
+            let cycles = cpu.run();
+            let cycles_bus = cpu.memoire.cycles.borrow().len() as u32 * 4;
+            assert_eq!(cycles, cycles_bus, "op code {:?} {:02X}", prefixe, op_code);
+        }
+    }
+}
+
+// STOP lit son second octet : deux cycles machine.
+#[test]
+fn stop_lit_son_second_octet() {
+    let mut cpu = Cpu::new(MemoireTest::new());
+    cpu.memoire.ram[0x0100] = 0x10;
+    assert_eq!(cpu.run(), 8);
+    assert_eq!(cpu.memoire.cycles.borrow().len(), 2);
+    assert_eq!(cpu.registres.pc, 0x0102);
+    assert!(cpu.stopped);
+}
+
+// IME n'est actif qu'après l'instruction qui suit EI.
+#[test]
+fn ei_retarde_d_une_instruction() {
+    let mut cpu = Cpu::new(MemoireTest::new());
+    cpu.memoire.ram[0x0100] = 0xFB;
+    cpu.memoire.ram[0xFFFF] = 0x01;
+    cpu.memoire.ram[0xFF0F] = 0x01;
+    cpu.run();
+    assert!(!cpu.ei);
+    cpu.run();
+    assert_eq!(cpu.registres.pc, 0x0102);
+    assert!(cpu.ei);
+    assert_eq!(cpu.run(), 20);
+    assert_eq!(cpu.registres.pc, 0x0040);
+    assert_eq!(cpu.memoire.ram[0xFF0F], 0x00);
+}
+
+// HALT avec IME inactif et une interruption en attente : l'octet suivant est lu deux fois.
+#[test]
+fn halt_bug() {
+    let mut cpu = Cpu::new(MemoireTest::new());
+    cpu.memoire.ram[0x0100..0x0102].copy_from_slice(&[0x76, 0x3C]);
+    cpu.memoire.ram[0xFFFF] = 0x01;
+    cpu.memoire.ram[0xFF0F] = 0x01;
+    cpu.run();
+    assert!(!cpu.halted);
+    cpu.run();
+    cpu.run();
+    assert_eq!(cpu.registres.a, 2);
+    assert_eq!(cpu.registres.pc, 0x0102);
+}
+
+// L'empilement de l'octet de poids fort de PC écrit dans IE et annule l'interruption : le CPU saute
+// en 0x0000 et IF reste inchangé.
+#[test]
+fn ecriture_de_ie_annule_l_interruption() {
+    let mut cpu = Cpu::new(MemoireTest::new());
+    cpu.ei = true;
+    cpu.registres.sp = 0x0000;
+    cpu.memoire.ram[0xFFFF] = 0x02;
+    cpu.memoire.ram[0xFF0F] = 0x02;
+    assert_eq!(cpu.run(), 20);
+    assert_eq!(cpu.memoire.ram[0xFFFF], 0x01);
+    assert_eq!(cpu.registres.pc, 0x0000);
+    assert_eq!(cpu.memoire.ram[0xFF0F], 0x02);
+}
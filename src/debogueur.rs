@@ -0,0 +1,371 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::{CouleurPalette, Gameboy, HookEvent, HookId, HookKind, SourcePalette};
+
+const AIDE: &str = "\
+commandes :
+  break <cible>        ajoute un breakpoint (label, BB:AAAA ou AAAA)
+  watch <cible|plage>  s'arrête après une écriture (plage DEBUT-FIN)
+  rwatch <cible|plage> s'arrête après une lecture
+  delete <n>           supprime le breakpoint ou watchpoint n
+  list                 liste les breakpoints et watchpoints
+  continue             reprend l'émulation
+  step [n]             exécute n instructions
+  regs                 affiche les registres
+  x <cible> [n]        affiche n octets de la mémoire
+  disas [cible] [n]    désassemble n instructions
+  oam                  liste les 40 entrées de l'OAM
+  palettes             affiche les palettes DMG et CGB
+  palette <p> <i> <v>  change la couleur i de la palette p (bgp, obp0, obp1, fond <n>,
+                       sprites <n>) : nuance 0-3 ou RGB555 en hexadécimal
+  quit                 quitte l'émulateur";
+
+// Résultat d'une commande du débogueur.
+pub enum Reponse {
+    // L'émulation reste en pause, le texte est à afficher.
+    Pause(String),
+    Reprise,
+    Quitter,
+}
+
+enum Point {
+    Breakpoint { bank: Option<usize>, addr: u16 },
+    Watchpoint { hook: HookId },
+}
+
+struct PointArret {
+    id: usize,
+    cible: String,
+    point: Point,
+}
+
+pub struct Debogueur {
+    pause: Arc<AtomicBool>,
+    // Dernier accès ayant déclenché un watchpoint.
+    acces: Arc<Mutex<Option<HookEvent>>>,
+    points: Vec<PointArret>,
+    next_id: usize,
+    pas_restants: u32,
+    // Permet de repartir d'un breakpoint sans s'y arrêter de nouveau.
+    ignorer_breakpoint: bool,
+    breakpoint_logiciel: bool,
+}
+
+impl Default for Debogueur {
+    fn default() -> Debogueur {
+        Debogueur::new()
+    }
+}
+
+impl Debogueur {
+    pub fn new() -> Debogueur {
+        Debogueur {
+            pause: Arc::new(AtomicBool::new(false)),
+            acces: Arc::new(Mutex::new(None)),
+            points: Vec::new(),
+            next_id: 1,
+            pas_restants: 0,
+            ignorer_breakpoint: false,
+            breakpoint_logiciel: false,
+        }
+    }
+
+    pub fn en_pause(&self) -> bool {
+        self.pause.load(Ordering::Relaxed)
+    }
+
+    pub fn pauser(&self) {
+        self.pause.store(true, Ordering::Relaxed);
+    }
+
+    fn reprendre(&mut self) -> Reponse {
+        self.ignorer_breakpoint = true;
+        self.pause.store(false, Ordering::Relaxed);
+        Reponse::Reprise
+    }
+
+    // À appeler avant chaque instruction : met l'émulation en pause sur un breakpoint.
+    pub fn avant_step(&mut self, gameboy: &Gameboy) {
+        if std::mem::take(&mut self.ignorer_breakpoint) {
+            return;
+        }
+        let pc = gameboy.get_registres().pc;
+        let bank = gameboy.get_bank(pc);
+        let atteint = self.points.iter().any(|point| match point.point {
+            Point::Breakpoint { bank: b, addr } => {
+                addr == pc && (b.is_none() || bank.is_none() || b == bank)
+            }
+            Point::Watchpoint { .. } => false,
+        });
+        if atteint {
+            self.pauser();
+        }
+    }
+
+    // À appeler après chaque instruction pour l'exécution pas à pas et les `LD B, B`.
+    pub fn apres_step(&mut self, gameboy: &mut Gameboy) {
+        if gameboy.take_breakpoint_logiciel() {
+            self.breakpoint_logiciel = true;
+            self.pauser();
+        }
+        if self.pas_restants > 0 {
+            self.pas_restants -= 1;
+            if self.pas_restants == 0 {
+                self.pauser();
+            }
+        }
+    }
+
+    // Texte affiché à l'entrée en pause : la cause et l'instruction courante.
+    pub fn invite(&mut self, gameboy: &Gameboy) -> String {
+        let mut texte = String::new();
+        if std::mem::take(&mut self.breakpoint_logiciel) {
+            texte += "breakpoint logiciel (LD B, B)\n";
+        }
+        if let Some(event) = self.acces.lock().unwrap().take() {
+            texte += &format!(
+                "watchpoint : {:?} {} = ${:02X}\n",
+                event.kind,
+                gameboy.formater_adresse(event.addr),
+                event.value
+            );
+        }
+        let pc = gameboy.get_registres().pc;
+        texte += &format!(
+            "{} {}",
+            gameboy.formater_position(pc),
+            gameboy.desassembler(pc).texte
+        );
+        texte
+    }
+
+    pub fn executer(&mut self, gameboy: &mut Gameboy, commande: &str) -> Reponse {
+        let mut mots = commande.split_whitespace();
+        let Some(nom) = mots.next() else {
+            return Reponse::Pause(String::new());
+        };
+        let arguments: Vec<&str> = mots.collect();
+        match (nom, arguments.as_slice()) {
+            ("b" | "break", [cible]) => self.ajouter_breakpoint(gameboy, cible),
+            ("w" | "watch", [cible]) => self.ajouter_watchpoint(gameboy, cible, HookKind::Write),
+            ("rw" | "rwatch", [cible]) => self.ajouter_watchpoint(gameboy, cible, HookKind::Read),
+            ("d" | "delete", [id]) => self.supprimer(gameboy, id),
+            ("l" | "list", []) => Reponse::Pause(self.lister()),
+            ("c" | "continue", []) => self.reprendre(),
+            ("s" | "step", arguments) => {
+                self.pas_restants = match arguments.first() {
+                    Some(n) => match n.parse() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Reponse::Pause(format!("nombre invalide : {}", n)),
+                    },
+                    None => 1,
+                };
+                self.reprendre()
+            }
+            ("r" | "regs", []) => Reponse::Pause(Self::registres(gameboy)),
+            ("x", [cible, arguments @ ..]) if arguments.len() <= 1 => {
+                let n = arguments.first().and_then(|n| n.parse().ok()).unwrap_or(16);
+                match gameboy.resoudre_adresse(cible) {
+                    Some((_, addr)) => Reponse::Pause(Self::memoire(gameboy, addr, n)),
+                    None => Reponse::Pause(format!("adresse inconnue : {}", cible)),
+                }
+            }
+            ("disas", arguments) if arguments.len() <= 2 => {
+                let addr = match arguments.first() {
+                    Some(cible) => match gameboy.resoudre_adresse(cible) {
+                        Some((_, addr)) => addr,
+                        None => return Reponse::Pause(format!("adresse inconnue : {}", cible)),
+                    },
+                    None => gameboy.get_registres().pc,
+                };
+                let n = arguments.get(1).and_then(|n| n.parse().ok()).unwrap_or(8);
+                Reponse::Pause(Self::desassembler(gameboy, addr, n))
+            }
+            ("oam", []) => Reponse::Pause(Self::oam(gameboy)),
+            ("palettes", []) => Reponse::Pause(Self::palettes(gameboy)),
+            ("palette", arguments) => Self::modifier_palette(gameboy, arguments),
+            ("q" | "quit", []) => Reponse::Quitter,
+            _ => Reponse::Pause(AIDE.to_string()),
+        }
+    }
+
+    fn ajouter_point(&mut self, cible: &str, point: Point) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.points.push(PointArret {
+            id,
+            cible: cible.to_string(),
+            point,
+        });
+        id
+    }
+
+    fn ajouter_breakpoint(&mut self, gameboy: &Gameboy, cible: &str) -> Reponse {
+        let Some((bank, addr)) = gameboy.resoudre_adresse(cible) else {
+            return Reponse::Pause(format!("adresse inconnue : {}", cible));
+        };
+        let id = self.ajouter_point(cible, Point::Breakpoint { bank, addr });
+        Reponse::Pause(format!("breakpoint {} : {}", id, cible))
+    }
+
+    fn ajouter_watchpoint(&mut self, gameboy: &mut Gameboy, cible: &str, kind: HookKind) -> Reponse {
+        let plage = match cible.split_once('-') {
+            Some((debut, fin)) => gameboy
+                .resoudre_adresse(debut)
+                .zip(gameboy.resoudre_adresse(fin))
+                .map(|((bank, debut), (_, fin))| (bank, debut..=fin)),
+            None => gameboy
+                .resoudre_adresse(cible)
+                .map(|(bank, addr)| (bank, addr..=addr)),
+        };
+        let Some((bank, plage)) = plage else {
+            return Reponse::Pause(format!("adresse inconnue : {}", cible));
+        };
+        let pause = self.pause.clone();
+        let acces = self.acces.clone();
+        let hook = gameboy.add_hook(kind, plage, bank, move |event| {
+            *acces.lock().unwrap() = Some(*event);
+            pause.store(true, Ordering::Relaxed);
+        });
+        let id = self.ajouter_point(cible, Point::Watchpoint { hook });
+        Reponse::Pause(format!("watchpoint {} : {}", id, cible))
+    }
+
+    fn supprimer(&mut self, gameboy: &mut Gameboy, id: &str) -> Reponse {
+        let Some(index) = self
+            .points
+            .iter()
+            .position(|point| id.parse() == Ok(point.id))
+        else {
+            return Reponse::Pause(format!("point d'arrêt inconnu : {}", id));
+        };
+        let point = self.points.remove(index);
+        if let Point::Watchpoint { hook } = point.point {
+            gameboy.remove_hook(hook);
+        }
+        Reponse::Pause(format!("supprimé : {}", point.cible))
+    }
+
+    fn lister(&self) -> String {
+        self.points
+            .iter()
+            .map(|point| {
+                let nature = match point.point {
+                    Point::Breakpoint { .. } => "breakpoint",
+                    Point::Watchpoint { .. } => "watchpoint",
+                };
+                format!("{} {} : {}", nature, point.id, point.cible)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn oam(gameboy: &Gameboy) -> String {
+        gameboy
+            .get_entrees_oam()
+            .iter()
+            .map(|entree| entree.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn palettes(gameboy: &Gameboy) -> String {
+        let dmg = [SourcePalette::Bgp, SourcePalette::Obp0, SourcePalette::Obp1];
+        let cgb = (0..8).map(SourcePalette::Fond).chain((0..8).map(SourcePalette::Sprites));
+        let mut lignes = Vec::new();
+        for source in dmg {
+            let nuances: Vec<u16> = (0..4)
+                .map(|index| gameboy.get_valeur_couleur(CouleurPalette { source, index }))
+                .collect();
+            let registre = nuances.iter().rev().fold(0, |registre, nuance| registre << 2 | nuance);
+            lignes.push(format!(
+                "{:<10} ${:02X} : {} {} {} {}",
+                source.to_string(), registre, nuances[0], nuances[1], nuances[2], nuances[3]
+            ));
+        }
+        for source in cgb {
+            let couleurs: Vec<String> = (0..4)
+                .map(|index| format!("{:04X}", gameboy.get_valeur_couleur(CouleurPalette { source, index })))
+                .collect();
+            lignes.push(format!("{:<10} {}", source.to_string(), couleurs.join(" ")));
+        }
+        lignes.join("\n")
+    }
+
+    fn modifier_palette(gameboy: &mut Gameboy, arguments: &[&str]) -> Reponse {
+        let (source, arguments) = match arguments {
+            ["bgp", arguments @ ..] => (SourcePalette::Bgp, arguments),
+            ["obp0", arguments @ ..] => (SourcePalette::Obp0, arguments),
+            ["obp1", arguments @ ..] => (SourcePalette::Obp1, arguments),
+            [nom @ ("fond" | "sprites"), palette, arguments @ ..] => match palette.parse() {
+                Ok(palette) if palette < 8 && *nom == "fond" => (SourcePalette::Fond(palette), arguments),
+                Ok(palette) if palette < 8 => (SourcePalette::Sprites(palette), arguments),
+                _ => return Reponse::Pause(format!("palette invalide : {}", palette)),
+            },
+            _ => return Reponse::Pause(AIDE.to_string()),
+        };
+        let [index, valeur] = arguments else {
+            return Reponse::Pause(AIDE.to_string());
+        };
+        let index = match index.parse() {
+            Ok(index) if index < 4 => index,
+            _ => return Reponse::Pause(format!("couleur invalide : {}", index)),
+        };
+        let maximum = match source {
+            SourcePalette::Bgp | SourcePalette::Obp0 | SourcePalette::Obp1 => 0x03,
+            // BCPD et OCPD n'existent pas sur DMG.
+            SourcePalette::Fond(_) | SourcePalette::Sprites(_) if !gameboy.is_cgb() => {
+                return Reponse::Pause("palettes CGB absentes en mode DMG".to_string())
+            }
+            SourcePalette::Fond(_) | SourcePalette::Sprites(_) => 0x7FFF,
+        };
+        let valeur = match u16::from_str_radix(valeur.trim_start_matches('$'), 16) {
+            Ok(valeur) if valeur <= maximum => valeur,
+            _ => return Reponse::Pause(format!("valeur invalide : {}", valeur)),
+        };
+        let couleur = CouleurPalette { source, index };
+        gameboy.set_valeur_couleur(couleur, valeur);
+        Reponse::Pause(format!("{} couleur {} = {:X}", source, index, valeur))
+    }
+
+    fn registres(gameboy: &Gameboy) -> String {
+        let registres = gameboy.get_registres();
+        format!(
+            "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X}",
+            registres.af(),
+            registres.bc(),
+            registres.de(),
+            registres.hl(),
+            registres.sp,
+            registres.pc,
+        )
+    }
+
+    fn memoire(gameboy: &Gameboy, addr: u16, n: u16) -> String {
+        let mut lignes = Vec::new();
+        for debut in (0..n).step_by(16) {
+            let debut = addr.wrapping_add(debut);
+            let octets: Vec<String> = (0..16.min(n - (debut.wrapping_sub(addr))))
+                .map(|i| format!("{:02X}", gameboy.get_octet_memoire(debut.wrapping_add(i))))
+                .collect();
+            lignes.push(format!("{:04X}: {}", debut, octets.join(" ")));
+        }
+        lignes.join("\n")
+    }
+
+    fn desassembler(gameboy: &Gameboy, addr: u16, n: usize) -> String {
+        let mut lignes = Vec::new();
+        let mut addr = addr;
+        for _ in 0..n {
+            let instruction = gameboy.desassembler(addr);
+            lignes.push(format!(
+                "{:<28} {}",
+                gameboy.formater_position(addr),
+                instruction.texte
+            ));
+            addr = addr.wrapping_add(instruction.longueur);
+        }
+        lignes.join("\n")
+    }
+}
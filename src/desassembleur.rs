@@ -0,0 +1,134 @@
+// Désassembleur SM83 : traduit les octets d'une instruction en mnémonique.
+// Les opérandes sont notées comme dans les tables d'op codes :
+//  d8  : valeur immédiate sur un octet
+//  d16 : valeur immédiate sur deux octets
+//  a8  : adresse 0xFF00 + valeur immédiate sur un octet
+//  a16 : adresse immédiate sur deux octets
+//  r8  : décalage signé sur un octet, relatif à l'instruction suivante
+
+const MNEMONIQUES: [&str; 256] = [
+    "NOP", "LD BC, d16", "LD (BC), A", "INC BC", // 00
+    "INC B", "DEC B", "LD B, d8", "RLCA", // 04
+    "LD (a16), SP", "ADD HL, BC", "LD A, (BC)", "DEC BC", // 08
+    "INC C", "DEC C", "LD C, d8", "RRCA", // 0c
+    "STOP d8", "LD DE, d16", "LD (DE), A", "INC DE", // 10
+    "INC D", "DEC D", "LD D, d8", "RLA", // 14
+    "JR r8", "ADD HL, DE", "LD A, (DE)", "DEC DE", // 18
+    "INC E", "DEC E", "LD E, d8", "RRA", // 1c
+    "JR NZ, r8", "LD HL, d16", "LD (HL+), A", "INC HL", // 20
+    "INC H", "DEC H", "LD H, d8", "DAA", // 24
+    "JR Z, r8", "ADD HL, HL", "LD A, (HL+)", "DEC HL", // 28
+    "INC L", "DEC L", "LD L, d8", "CPL", // 2c
+    "JR NC, r8", "LD SP, d16", "LD (HL-), A", "INC SP", // 30
+    "INC (HL)", "DEC (HL)", "LD (HL), d8", "SCF", // 34
+    "JR C, r8", "ADD HL, SP", "LD A, (HL-)", "DEC SP", // 38
+    "INC A", "DEC A", "LD A, d8", "CCF", // 3c
+    "LD B, B", "LD B, C", "LD B, D", "LD B, E", // 40
+    "LD B, H", "LD B, L", "LD B, (HL)", "LD B, A", // 44
+    "LD C, B", "LD C, C", "LD C, D", "LD C, E", // 48
+    "LD C, H", "LD C, L", "LD C, (HL)", "LD C, A", // 4c
+    "LD D, B", "LD D, C", "LD D, D", "LD D, E", // 50
+    "LD D, H", "LD D, L", "LD D, (HL)", "LD D, A", // 54
+    "LD E, B", "LD E, C", "LD E, D", "LD E, E", // 58
+    "LD E, H", "LD E, L", "LD E, (HL)", "LD E, A", // 5c
+    "LD H, B", "LD H, C", "LD H, D", "LD H, E", // 60
+    "LD H, H", "LD H, L", "LD H, (HL)", "LD H, A", // 64
+    "LD L, B", "LD L, C", "LD L, D", "LD L, E", // 68
+    "LD L, H", "LD L, L", "LD L, (HL)", "LD L, A", // 6c
+    "LD (HL), B", "LD (HL), C", "LD (HL), D", "LD (HL), E", // 70
+    "LD (HL), H", "LD (HL), L", "HALT", "LD (HL), A", // 74
+    "LD A, B", "LD A, C", "LD A, D", "LD A, E", // 78
+    "LD A, H", "LD A, L", "LD A, (HL)", "LD A, A", // 7c
+    "ADD A, B", "ADD A, C", "ADD A, D", "ADD A, E", // 80
+    "ADD A, H", "ADD A, L", "ADD A, (HL)", "ADD A, A", // 84
+    "ADC A, B", "ADC A, C", "ADC A, D", "ADC A, E", // 88
+    "ADC A, H", "ADC A, L", "ADC A, (HL)", "ADC A, A", // 8c
+    "SUB B", "SUB C", "SUB D", "SUB E", // 90
+    "SUB H", "SUB L", "SUB (HL)", "SUB A", // 94
+    "SBC A, B", "SBC A, C", "SBC A, D", "SBC A, E", // 98
+    "SBC A, H", "SBC A, L", "SBC A, (HL)", "SBC A, A", // 9c
+    "AND B", "AND C", "AND D", "AND E", // a0
+    "AND H", "AND L", "AND (HL)", "AND A", // a4
+    "XOR B", "XOR C", "XOR D", "XOR E", // a8
+    "XOR H", "XOR L", "XOR (HL)", "XOR A", // ac
+    "OR B", "OR C", "OR D", "OR E", // b0
+    "OR H", "OR L", "OR (HL)", "OR A", // b4
+    "CP B", "CP C", "CP D", "CP E", // b8
+    "CP H", "CP L", "CP (HL)", "CP A", // bc
+    "RET NZ", "POP BC", "JP NZ, a16", "JP a16", // c0
+    "CALL NZ, a16", "PUSH BC", "ADD A, d8", "RST 00H", // c4
+    "RET Z", "RET", "JP Z, a16", "PREFIX CB", // c8
+    "CALL Z, a16", "CALL a16", "ADC A, d8", "RST 08H", // cc
+    "RET NC", "POP DE", "JP NC, a16", "-", // d0
+    "CALL NC, a16", "PUSH DE", "SUB d8", "RST 10H", // d4
+    "RET C", "RETI", "JP C, a16", "-", // d8
+    "CALL C, a16", "-", "SBC A, d8", "RST 18H", // dc
+    "LDH (a8), A", "POP HL", "LD (C), A", "-", // e0
+    "-", "PUSH HL", "AND d8", "RST 20H", // e4
+    "ADD SP, r8", "JP (HL)", "LD (a16), A", "-", // e8
+    "-", "-", "XOR d8", "RST 28H", // ec
+    "LDH A, (a8)", "POP AF", "LD A, (C)", "DI", // f0
+    "-", "PUSH AF", "OR d8", "RST 30H", // f4
+    "LD HL, SP+r8", "LD SP, HL", "LD A, (a16)", "EI", // f8
+    "-", "-", "CP d8", "RST 38H", // fc
+];
+
+const CB_REGISTRES: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const CB_ROTATIONS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+pub struct Instruction {
+    pub texte: String,
+    pub longueur: u16,
+}
+
+// `lire` renvoie l'octet à une adresse, `formater_adresse` la représentation d'une adresse
+// cible (label ou valeur hexadécimale).
+pub fn desassembler<L, F>(lire: L, addr: u16, formater_adresse: F) -> Instruction
+where
+    L: Fn(u16) -> u8,
+    F: Fn(u16) -> String,
+{
+    let op_code = lire(addr);
+    if op_code == 0xCB {
+        let cb_code = lire(addr.wrapping_add(1));
+        let registre = CB_REGISTRES[(cb_code & 0x07) as usize];
+        let bit = (cb_code >> 3) & 0x07;
+        let texte = match cb_code {
+            0x00..=0x3F => format!("{} {}", CB_ROTATIONS[bit as usize], registre),
+            0x40..=0x7F => format!("BIT {}, {}", bit, registre),
+            0x80..=0xBF => format!("RES {}, {}", bit, registre),
+            _ => format!("SET {}, {}", bit, registre),
+        };
+        return Instruction { texte, longueur: 2 };
+    }
+
+    let mnemonique = MNEMONIQUES[op_code as usize];
+    if mnemonique == "-" {
+        return Instruction {
+            texte: format!("DB ${:02X}", op_code),
+            longueur: 1,
+        };
+    }
+    let octet = lire(addr.wrapping_add(1));
+    let mot = u16::from(octet) | (u16::from(lire(addr.wrapping_add(2))) << 8);
+    let (texte, longueur) = if mnemonique.contains("d16") {
+        (mnemonique.replace("d16", &formater_adresse(mot)), 3)
+    } else if mnemonique.contains("a16") {
+        (mnemonique.replace("a16", &formater_adresse(mot)), 3)
+    } else if mnemonique.contains("d8") {
+        (mnemonique.replace("d8", &format!("${:02X}", octet)), 2)
+    } else if mnemonique.contains("a8") {
+        (mnemonique.replace("a8", &formater_adresse(0xFF00 | u16::from(octet))), 2)
+    } else if mnemonique.starts_with("JR") {
+        let cible = addr.wrapping_add(2).wrapping_add(octet as i8 as u16);
+        (mnemonique.replace("r8", &formater_adresse(cible)), 2)
+    } else if mnemonique.contains("+r8") {
+        // Signe explicite : SP+5, SP-5
+        (mnemonique.replace("+r8", &format!("{:+}", octet as i8)), 2)
+    } else if mnemonique.contains("r8") {
+        (mnemonique.replace("r8", &format!("{}", octet as i8)), 2)
+    } else {
+        (mnemonique.to_string(), 1)
+    };
+    Instruction { texte, longueur }
+}
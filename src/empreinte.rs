@@ -0,0 +1,41 @@
+// Empreinte FNV-1a sur 64 bits. Contrairement au hasher de la bibliothèque standard, sa valeur ne
+// dépend ni de la version de Rust ni de la plateforme : elle peut être enregistrée dans un fichier.
+const BASE: u64 = 0xCBF2_9CE4_8422_2325;
+const PREMIER: u64 = 0x0000_0100_0000_01B3;
+
+pub struct Empreinte {
+    valeur: u64,
+}
+
+impl Empreinte {
+    pub fn new() -> Empreinte {
+        Empreinte { valeur: BASE }
+    }
+
+    pub fn ecrire(&mut self, octets: &[u8]) {
+        for octet in octets {
+            self.valeur ^= u64::from(*octet);
+            self.valeur = self.valeur.wrapping_mul(PREMIER);
+        }
+    }
+
+    pub fn ecrire_u8(&mut self, valeur: u8) {
+        self.ecrire(&[valeur]);
+    }
+
+    pub fn ecrire_u16(&mut self, valeur: u16) {
+        self.ecrire(&valeur.to_le_bytes());
+    }
+
+    pub fn ecrire_u64(&mut self, valeur: u64) {
+        self.ecrire(&valeur.to_le_bytes());
+    }
+
+    pub fn ecrire_bool(&mut self, valeur: bool) {
+        self.ecrire_u8(u8::from(valeur));
+    }
+
+    pub fn valeur(&self) -> u64 {
+        self.valeur
+    }
+}
@@ -0,0 +1,94 @@
+use std::ops::RangeInclusive;
+
+// Type d'accès au bus observé par un hook.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HookKind {
+    Read,
+    Write,
+    Execute,
+}
+
+// Accès au bus transmis aux callbacks.
+//  bank : banque ROM (0x4000-0x7FFF) ou RAM cartouche (0xA000-0xBFFF) active au moment de l'accès,
+//         None pour les autres zones de la mémoire.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HookEvent {
+    pub kind: HookKind,
+    pub addr: u16,
+    pub bank: Option<usize>,
+    pub value: u8,
+}
+
+pub type HookId = usize;
+
+pub type HookCallback = Box<dyn FnMut(&HookEvent) + Send>;
+
+struct Hook {
+    id: HookId,
+    kind: HookKind,
+    range: RangeInclusive<u16>,
+    bank: Option<usize>,
+    callback: HookCallback,
+}
+
+impl Hook {
+    fn correspond(&self, event: &HookEvent) -> bool {
+        if self.kind != event.kind || !self.range.contains(&event.addr) {
+            return false;
+        }
+        // Un hook sans banque correspond à toutes les banques.
+        match (self.bank, event.bank) {
+            (Some(bank), Some(event_bank)) => bank == event_bank,
+            _ => true,
+        }
+    }
+}
+
+pub struct Hooks {
+    hooks: Vec<Hook>,
+    next_id: HookId,
+}
+
+impl Hooks {
+    pub fn new() -> Hooks {
+        Hooks {
+            hooks: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn add(
+        &mut self,
+        kind: HookKind,
+        range: RangeInclusive<u16>,
+        bank: Option<usize>,
+        callback: HookCallback,
+    ) -> HookId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.hooks.push(Hook {
+            id,
+            kind,
+            range,
+            bank,
+            callback,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: HookId) -> bool {
+        let len = self.hooks.len();
+        self.hooks.retain(|hook| hook.id != id);
+        len != self.hooks.len()
+    }
+
+    pub fn declencher(&mut self, event: &HookEvent) {
+        for hook in self.hooks.iter_mut().filter(|hook| hook.correspond(event)) {
+            (hook.callback)(event);
+        }
+    }
+}
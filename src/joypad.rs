@@ -1,5 +1,5 @@
+use crate::empreinte::Empreinte;
 use crate::memoire::Memoire;
-use crate::mmu::InterruptFlag;
 
 pub enum JoypadKey {
     Right = 0b0000_0001,
@@ -15,7 +15,6 @@ pub enum JoypadKey {
 pub struct Joypad {
     pub matrix: u8,
     pub select: u8,
-    pub interrupt: u8,
 }
 
 impl Joypad {
@@ -24,14 +23,23 @@ impl Joypad {
         Joypad {
             matrix: 0xFF,
             select: 0x00,
-            interrupt: InterruptFlag::None as u8,
         }
     }
 
     // Méthode pour signaler qu'une touche est enfoncée
     pub fn keydown(&mut self, key: JoypadKey) {
         self.matrix &= !(key as u8);
-        self.interrupt |= InterruptFlag::Joypad as u8;
+    }
+
+    // Une ligne P10-P13 sélectionnée est à l'état bas (touche enfoncée). Sans groupe
+    // sélectionné (P14 et P15 à 1), aucune ligne ne peut l'être.
+    pub fn is_ligne_basse(&self) -> bool {
+        (!self.select & 0x10 != 0 && self.matrix & 0x0F != 0x0F)
+            || (!self.select & 0x20 != 0 && self.matrix & 0xF0 != 0xF0)
+    }
+
+    pub fn empreinte(&self, empreinte: &mut Empreinte) {
+        empreinte.ecrire(&[self.matrix, self.select]);
     }
 
     // Méthode pour signaler qu'une touche est relâchée
@@ -1,15 +1,37 @@
 mod cartouches;
 mod cpu;
+pub mod debogueur;
+mod desassembleur;
+mod empreinte;
+mod hooks;
 mod joypad;
 mod memoire;
 mod mmu;
+mod planificateur;
+#[cfg(feature = "profilage")]
+mod profilage;
 mod ppu;
+pub mod regression;
+mod serie;
+mod strict;
+mod symboles;
 mod timer;
 
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::ops::RangeInclusive;
+
+pub use crate::cpu::registres::Registers;
+pub use crate::desassembleur::Instruction;
+pub use crate::hooks::{HookEvent, HookId, HookKind};
+pub use crate::ppu::visualiseur::{
+    Carreau, CouleurPalette, EntreeOam, Image, PaletteVue, SourcePalette,
+};
+pub use crate::ppu::Rendu;
+#[cfg(feature = "profilage")]
+pub use crate::profilage::Profil;
+pub use crate::strict::{Avertissement, Diagnostic};
+pub use crate::symboles::Symboles;
 
-use crate::memoire::Memoire;
 #[derive(Clone, Copy)]
 pub enum GameboyButton {
     Right,
@@ -37,41 +59,166 @@ impl From<GameboyButton> for joypad::JoypadKey {
     }
 }
 
+// État d'exécution de la console.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Status {
+    Running,
+    // Le CPU a exécuté un op code invalide et ne répond plus.
+    LockedUp { pc: u16, opcode: u8 },
+}
+
 pub struct Gameboy {
-    mmu: Rc<RefCell<mmu::Mmu>>,
-    cpu: cpu::RealTimeCpu,
+    cpu: cpu::RealTimeCpu<mmu::Mmu>,
+    symboles: Symboles,
+    // Adresse de la dernière instruction exécutée, pour les rapports de crash.
+    derniere_instruction: u16,
 }
 
+// La Gameboy peut être déplacée vers un autre thread (émulation hors du thread de la fenêtre).
+const _: () = {
+    const fn is_send<T: Send>() {}
+    is_send::<Gameboy>();
+};
+
 impl Gameboy {
 
     pub fn new(rom: Vec<u8>) -> Gameboy {
         let cartouche = cartouches::new(rom);
-        let mmu = Rc::new(RefCell::new(mmu::Mmu::new(cartouche)));
-        let cpu = cpu::RealTimeCpu::new(mmu.clone());
-        Gameboy { mmu, cpu }
+        let cpu = cpu::RealTimeCpu::new(mmu::Mmu::new(cartouche));
+        Gameboy {
+            cpu,
+            symboles: Symboles::new(),
+            derniere_instruction: 0x0100,
+        }
+    }
+
+    // Le bus appartient au CPU.
+    fn mmu(&self) -> &mmu::Mmu {
+        &self.cpu.cpu.memoire
+    }
+
+    fn mmu_mut(&mut self) -> &mut mmu::Mmu {
+        &mut self.cpu.cpu.memoire
     }
 
     pub fn step(&mut self) -> u32 {
-        if self.mmu.borrow().get_octet(self.cpu.cpu.registres.pc) == 0x10 {
-            self.mmu.borrow_mut().perform_vitesse_switch();
+        self.derniere_instruction = self.cpu.cpu.registres.pc;
+        if let Some(strict) = &self.mmu().strict {
+            let mut strict = strict.borrow_mut();
+            strict.pc = self.derniere_instruction;
+            strict.bank = self.get_bank_symbole(self.derniere_instruction);
+        }
+        let etait_arrete = self.cpu.cpu.stopped;
+        // Le CPU fait avancer le reste du système à chaque accès mémoire.
+        let mut cycles = self.cpu.run();
+        if self.cpu.cpu.stopped {
+            let processeur = &mut self.cpu.cpu;
+            if !etait_arrete {
+                processeur.memoire.reset_div();
+                // En mode CGB, STOP effectue le changement de vitesse armé par KEY1 :
+                // le CPU reste ensuite en pause le temps que l'horloge se stabilise.
+                if processeur.memoire.is_vitesse_switch_prepare() {
+                    processeur.memoire.perform_vitesse_switch();
+                    processeur.stopped = false;
+                    processeur.memoire.run_cycles(cpu::VITESSE_SWITCH_CYCLES);
+                    cycles += cpu::VITESSE_SWITCH_CYCLES;
+                }
+            } else if processeur.memoire.joypad.is_ligne_basse() {
+                processeur.stopped = false;
+            }
+            // En mode STOP, le PPU et le timer sont à l'arrêt.
+            return cycles;
         }
-        let cycles = self.cpu.run();
-        self.mmu.borrow_mut().run_cycles(cycles);
+        self.mmu().verifier_pile(self.cpu.cpu.registres.sp);
         cycles
     }
 
+    pub fn get_status(&self) -> Status {
+        match self.cpu.cpu.verrou {
+            Some((pc, opcode)) => Status::LockedUp { pc, opcode },
+            None => Status::Running,
+        }
+    }
+
     pub fn has_screen_updated(&mut self) -> bool {
-        let result = self.mmu.borrow().ppu.vblank;
-        self.mmu.borrow_mut().ppu.vblank = false;
-        result
+        std::mem::take(&mut self.mmu_mut().ppu.vblank)
     }
 
     pub fn get_screen_dimension(&self) -> [usize;2] {
-        return [ppu::SCREEN_HEIGHT, ppu::SCREEN_WIDTH];
+        [ppu::SCREEN_HEIGHT, ppu::SCREEN_WIDTH]
     }
     
     pub fn get_screen_data(&self) -> [ppu::Pixel; ppu::SCREEN_WIDTH * ppu::SCREEN_HEIGHT] {
-        self.mmu.borrow().ppu.data
+        self.mmu().ppu.get_ecran()
+    }
+
+    // Écran éteint par LCDC : aucune trame n'est signalée par has_screen_updated et
+    // get_screen_data renvoie une image blanche.
+    pub fn is_ecran_allume(&self) -> bool {
+        self.mmu().ppu.is_allume()
+    }
+
+    // Cartouche en mode CGB (0x0143, bit 7) : palettes CGB, deuxième banque de VRAM...
+    pub fn is_cgb(&self) -> bool {
+        self.mmu().cgb
+    }
+
+    // Vue de débogage des 384 carreaux des deux banques de VRAM.
+    pub fn get_image_carreaux(&self, palette: PaletteVue) -> Image {
+        self.mmu().ppu.get_image_carreaux(palette)
+    }
+
+    // Vue de débogage de la carte de carreaux à 9800 ou 9C00, avec les cadres de la partie
+    // affichée du fond et de la fenêtre.
+    pub fn get_image_carte(&self, carte: u16, cadres: bool) -> Image {
+        self.mmu().ppu.get_image_carte(carte, cadres)
+    }
+
+    // Entrées de l'OAM décodées, avec celles écartées par la limite de sprites par ligne sur la
+    // dernière trame.
+    pub fn get_entrees_oam(&self) -> Vec<EntreeOam> {
+        self.mmu().ppu.get_entrees_oam()
+    }
+
+    // Vue de débogage des 40 sprites de l'OAM.
+    pub fn get_image_sprites(&self) -> Image {
+        self.mmu().ppu.get_image_sprites()
+    }
+
+    // Vue de débogage des palettes DMG et CGB.
+    pub fn get_image_palettes(&self) -> Image {
+        self.mmu().ppu.get_image_palettes()
+    }
+
+    // Nuance de 0 à 3 pour BGP/OBP0/OBP1, RGB555 pour les palettes CGB.
+    pub fn get_valeur_couleur(&self, couleur: CouleurPalette) -> u16 {
+        self.mmu().ppu.get_valeur_couleur(couleur)
+    }
+
+    pub fn set_valeur_couleur(&mut self, couleur: CouleurPalette, valeur: u16) {
+        self.mmu_mut().set_valeur_couleur(couleur, valeur);
+    }
+
+    // Carte utilisée par la fenêtre (LCDC.6) ou le fond (LCDC.3) : 9800 ou 9C00.
+    pub fn get_carte(&self, fenetre: bool) -> u16 {
+        self.mmu().ppu.get_carte(fenetre)
+    }
+
+    // Empreinte de l'image affichée, stable d'une version à l'autre pour les tests de non-régression.
+    pub fn get_hash_ecran(&self) -> u64 {
+        let mut empreinte = empreinte::Empreinte::new();
+        for pixel in self.mmu().ppu.get_ecran().iter() {
+            empreinte.ecrire(&[pixel.r, pixel.g, pixel.b]);
+        }
+        empreinte.valeur()
+    }
+
+    // Empreinte de l'état complet de la machine : CPU, mémoires, périphériques et cartouche.
+    pub fn get_hash_etat(&self) -> u64 {
+        let mut empreinte = empreinte::Empreinte::new();
+        self.cpu.cpu.empreinte(&mut empreinte);
+        self.mmu().empreinte(&mut empreinte);
+        empreinte.valeur()
     }
 
     pub fn can_take_input(&mut self) -> bool {
@@ -79,10 +226,222 @@ impl Gameboy {
     }
 
     pub fn gerer_keyup(&mut self, button: GameboyButton) {
-        self.mmu.borrow_mut().joypad.keyup(button.into());
+        self.mmu_mut().joypad.keyup(button.into());
     }
 
     pub fn gerer_keydown(&mut self, button: GameboyButton) {
-        self.mmu.borrow_mut().joypad.keydown(button.into());
+        self.mmu_mut().keydown(button.into());
+    }
+
+    // Enregistre un callback appelé à chaque accès du CPU de type `kind` dans `range`.
+    // `bank` restreint le hook à une banque pour 0x4000-0x7FFF (ROM) et 0xA000-0xBFFF (RAM cartouche),
+    // il est ignoré ailleurs.
+    pub fn add_hook<F>(
+        &mut self,
+        kind: HookKind,
+        range: RangeInclusive<u16>,
+        bank: Option<usize>,
+        callback: F,
+    ) -> HookId
+    where
+        F: FnMut(&HookEvent) + Send + 'static,
+    {
+        self.mmu_mut()
+            .hooks
+            .get_mut()
+            .add(kind, range, bank, Box::new(callback))
+    }
+
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        self.mmu_mut().hooks.get_mut().remove(id)
+    }
+
+    // Accès à la mémoire sans déclencher les hooks.
+    pub fn get_octet_memoire(&self, addr: u16) -> u8 {
+        self.mmu().lire_octet(addr)
+    }
+
+    pub fn set_octet_memoire(&mut self, addr: u16, value: u8) {
+        self.mmu_mut().ecrire_octet(addr, value);
+    }
+
+    pub fn get_bank(&self, addr: u16) -> Option<usize> {
+        self.mmu().get_bank(addr)
+    }
+
+    pub fn get_registres(&self) -> Registers {
+        self.cpu.cpu.registres
+    }
+
+    // Active `LD B, B` comme breakpoint logiciel.
+    pub fn set_breakpoints_logiciels(&mut self, enabled: bool) {
+        self.cpu.cpu.breakpoints_logiciels = enabled;
+    }
+
+    // Renvoie true si un `LD B, B` a été exécuté depuis le dernier appel.
+    pub fn take_breakpoint_logiciel(&mut self) -> bool {
+        std::mem::take(&mut self.cpu.cpu.breakpoint_atteint)
+    }
+
+    // Active l'affichage des messages de debug `LD D, D` au format no$gmb.
+    pub fn set_messages_debug(&mut self, enabled: bool) {
+        self.cpu.cpu.messages_debug = enabled;
+    }
+
+    // Mode strict : les comportements illégaux sont relevés comme avertissements.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.mmu_mut().strict = if enabled {
+            Some(RefCell::new(strict::Strict::new()))
+        } else {
+            None
+        };
+    }
+
+    pub fn take_avertissements(&mut self) -> Vec<Avertissement> {
+        match &mut self.mmu_mut().strict {
+            Some(strict) => strict.get_mut().take_avertissements(),
+            None => Vec::new(),
+        }
+    }
+
+    // Octets envoyés sur le port série depuis le dernier appel.
+    pub fn take_sortie_serie(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.mmu_mut().serie.sortie)
+    }
+
+    // Désactive la limitation à la vitesse du matériel réel.
+    pub fn set_vitesse_limitee(&mut self, enabled: bool) {
+        self.cpu.limite = enabled;
+    }
+
+    // Rendu au dot près (pixel FIFO) ou par ligne entière.
+    pub fn set_rendu(&mut self, rendu: Rendu) {
+        self.mmu_mut().ppu.rendu = rendu;
+    }
+
+    // Désactive la limite de 10 sprites par ligne, pour supprimer le clignotement des jeux qui
+    // affichent leurs sprites à tour de rôle.
+    pub fn set_limite_sprites(&mut self, enabled: bool) {
+        self.mmu_mut().ppu.limite_sprites = enabled;
+    }
+
+    // Reproduit le bug du DMG : écrire dans STAT en HBlank, en VBlank ou sur coïncidence LY=LYC
+    // demande une interruption STAT. Sans effet sur CGB.
+    pub fn set_bug_stat(&mut self, enabled: bool) {
+        self.mmu_mut().ppu.bug_stat = enabled;
+    }
+
+    // Reproduit la corruption de l'OAM du DMG par les INC/DEC 16 bits pointant sur FE00-FEFF
+    // pendant le mode 2. Sans effet sur CGB.
+    pub fn set_corruption_oam(&mut self, enabled: bool) {
+        self.mmu_mut().ppu.corruption_oam = enabled;
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.cpu.cpu.halted
+    }
+
+    // Temps passé dans le PPU et la MMU depuis la mise sous tension.
+    #[cfg(feature = "profilage")]
+    pub fn get_profil(&self) -> Profil {
+        self.mmu().chronometre.get_profil()
+    }
+
+    // Charge le contenu d'un fichier .sym de RGBDS.
+    pub fn charger_symboles(&mut self, contenu: &str) {
+        self.symboles = Symboles::parse(contenu);
+    }
+
+    pub fn get_symboles(&self) -> &Symboles {
+        &self.symboles
+    }
+
+    // Adresse d'un label, ou d'une position "BB:AAAA" / "AAAA" en hexadécimal.
+    pub fn resoudre_adresse(&self, cible: &str) -> Option<(Option<usize>, u16)> {
+        if let Some((bank, addr)) = self.symboles.get_adresse(cible) {
+            let bank = self.get_bank(addr).map(|_| bank);
+            return Some((bank, addr));
+        }
+        let parse = |s: &str| u16::from_str_radix(s.trim_start_matches('$'), 16).ok();
+        match cible.split_once(':') {
+            Some((bank, addr)) => Some((Some(usize::from_str_radix(bank, 16).ok()?), parse(addr)?)),
+            None => Some((None, parse(cible)?)),
+        }
+    }
+
+    fn get_bank_symbole(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.get_bank(addr).unwrap_or(0),
+        }
+    }
+
+    // Label exact d'une adresse, sinon sa valeur hexadécimale.
+    pub fn formater_adresse(&self, addr: u16) -> String {
+        match self.symboles.get_label(self.get_bank_symbole(addr), addr) {
+            Some(label) => label.to_string(),
+            None => format!("${:04X}", addr),
+        }
+    }
+
+    // Position dans le code : "BB:AAAA" suivi du label le plus proche s'il existe.
+    pub fn formater_position(&self, addr: u16) -> String {
+        self.formater_position_bank(self.get_bank_symbole(addr), addr)
+    }
+
+    pub fn formater_position_bank(&self, bank: usize, addr: u16) -> String {
+        self.symboles.formater(bank, addr)
+    }
+
+    pub fn desassembler(&self, addr: u16) -> Instruction {
+        desassembleur::desassembler(
+            |addr| self.get_octet_memoire(addr),
+            addr,
+            |cible| self.formater_adresse(cible),
+        )
+    }
+
+    // Ligne de trace de l'instruction sur le point d'être exécutée.
+    pub fn trace(&self) -> String {
+        let registres = self.get_registres();
+        format!(
+            "{:<28} {:<20} AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X}",
+            self.formater_position(registres.pc),
+            self.desassembler(registres.pc).texte,
+            registres.af(),
+            registres.bc(),
+            registres.de(),
+            registres.hl(),
+            registres.sp,
+        )
+    }
+
+    // Rapport affiché lorsque l'émulation s'interrompt sur une erreur.
+    pub fn rapport_crash(&self) -> String {
+        let registres = self.get_registres();
+        let mut rapport = format!(
+            "crash à {}\nAF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X}\n",
+            self.formater_position(self.derniere_instruction),
+            registres.af(),
+            registres.bc(),
+            registres.de(),
+            registres.hl(),
+            registres.sp,
+            registres.pc,
+        );
+        let mut addr = self.derniere_instruction;
+        for _ in 0..4 {
+            let instruction = self.desassembler(addr);
+            rapport += &format!("  {:<28} {}\n", self.formater_position(addr), instruction.texte);
+            addr = addr.wrapping_add(instruction.longueur);
+        }
+        rapport += "pile :\n";
+        for i in 0..4 {
+            let addr = registres.sp.wrapping_add(i * 2);
+            let valeur = u16::from(self.get_octet_memoire(addr))
+                | (u16::from(self.get_octet_memoire(addr.wrapping_add(1))) << 8);
+            rapport += &format!("  {:04X}: {}\n", addr, self.formater_position(valeur));
+        }
+        rapport
     }
 }
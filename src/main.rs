@@ -1,9 +1,19 @@
-use std::fs::File;
-use std::io::Read;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Write};
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use argparse::{ArgumentParser, Store};
-use minifb::{Key, Scale, Window, WindowOptions};
-use emulateur_gameboy::{GameboyButton, Gameboy};
+use argparse::{ArgumentParser, Collect, Store, StoreTrue};
+use minifb::{Key, KeyRepeat, MouseMode, Scale, Window, WindowOptions};
+use emulateur_gameboy::debogueur::{Debogueur, Reponse};
+use emulateur_gameboy::{
+    Carreau, CouleurPalette, EntreeOam, GameboyButton, Gameboy, HookEvent, HookKind, Image,
+    PaletteVue, Rendu, SourcePalette, Status,
+};
 
 const KEY_MAPPINGS: [(Key, GameboyButton); 8] = [
     (Key::Right, GameboyButton::Right),
@@ -16,8 +26,266 @@ const KEY_MAPPINGS: [(Key, GameboyButton); 8] = [
     (Key::Enter, GameboyButton::Start),
 ];
 
+// Lit une adresse "C000" ou une plage "C000-C0FF" en hexadécimal.
+fn parse_plage(value: &str) -> Option<RangeInclusive<u16>> {
+    let parse = |s: &str| u16::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+    match value.split_once('-') {
+        Some((debut, fin)) => Some(parse(debut)?..=parse(fin)?),
+        None => parse(value).map(|addr| addr..=addr),
+    }
+}
+
+fn afficher_acces(event: &HookEvent) {
+    let bank = match event.bank {
+        Some(bank) => format!("{:02X}:", bank),
+        None => String::new(),
+    };
+    eprintln!("watch: {:?} {}{:04X} = {:02X}", event.kind, bank, event.addr, event.value);
+}
+
+// Convertit une image RGBA du cœur au format 0RGB de minifb.
+fn convertir_image(image: &Image, buffer: &mut Vec<u32>) {
+    buffer.clear();
+    buffer.extend(image.rgba.chunks_exact(4).map(|pixel| {
+        0xFF00_0000 | u32::from(pixel[0]) << 16 | u32::from(pixel[1]) << 8 | u32::from(pixel[2])
+    }));
+}
+
+// Enregistre une image du cœur au format PNG, avec la feature `export_png`.
+#[cfg(feature = "export_png")]
+fn enregistrer_png(image: &Image, chemin: &str) -> Result<(), String> {
+    let fichier = File::create(chemin).map_err(|erreur| erreur.to_string())?;
+    let mut encodeur =
+        png::Encoder::new(io::BufWriter::new(fichier), image.largeur as u32, image.hauteur as u32);
+    encodeur.set_color(png::ColorType::Rgba);
+    encodeur.set_depth(png::BitDepth::Eight);
+    encodeur
+        .write_header()
+        .and_then(|mut ecrivain| ecrivain.write_image_data(&image.rgba))
+        .map_err(|erreur| erreur.to_string())
+}
+
+#[cfg(not(feature = "export_png"))]
+fn enregistrer_png(_image: &Image, _chemin: &str) -> Result<(), String> {
+    Err("export PNG indisponible, compiler avec --features export_png".to_string())
+}
+
+// Fenêtre des carreaux de la VRAM : P change de palette, le titre indique le carreau sous la
+// souris.
+struct FenetreCarreaux {
+    window: Window,
+    buffer: Vec<u32>,
+    palette: PaletteVue,
+}
+
+impl FenetreCarreaux {
+    fn new() -> FenetreCarreaux {
+        let options = WindowOptions {
+            scale: Scale::X2,
+            ..Default::default()
+        };
+        FenetreCarreaux {
+            window: Window::new("Carreaux VRAM", 256, 192, options).unwrap(),
+            buffer: Vec::new(),
+            palette: PaletteVue::Gris,
+        }
+    }
+
+    fn actualiser(&mut self, gameboy: &Gameboy) {
+        if self.window.is_key_pressed(Key::P, KeyRepeat::No) {
+            self.palette = self.palette.suivante();
+        }
+        let image = gameboy.get_image_carreaux(self.palette);
+        convertir_image(&image, &mut self.buffer);
+        let carreau = self
+            .window
+            .get_mouse_pos(MouseMode::Discard)
+            .and_then(|(x, y)| Carreau::sous(x as usize, y as usize));
+        let titre = match carreau {
+            Some(carreau) => format!(
+                "Carreaux VRAM - {:?} - banque {} carreau ${:03X} ({:04X})",
+                self.palette, carreau.banque, carreau.index, carreau.adresse
+            ),
+            None => format!("Carreaux VRAM - {:?}", self.palette),
+        };
+        self.window.set_title(&titre);
+        self.window.update_with_buffer(&self.buffer, image.largeur, image.hauteur).unwrap();
+    }
+}
+
+// Fenêtre de la carte du fond : M alterne entre 9800 et 9C00, C masque les cadres de la partie
+// affichée, S enregistre l'image en PNG dans le répertoire courant.
+struct FenetreCarte {
+    window: Window,
+    buffer: Vec<u32>,
+    carte: u16,
+    cadres: bool,
+}
+
+impl FenetreCarte {
+    fn new(gameboy: &Gameboy) -> FenetreCarte {
+        let options = WindowOptions {
+            scale: Scale::X2,
+            ..Default::default()
+        };
+        FenetreCarte {
+            window: Window::new("Carte", 256, 256, options).unwrap(),
+            buffer: Vec::new(),
+            carte: gameboy.get_carte(false),
+            cadres: true,
+        }
+    }
+
+    fn actualiser(&mut self, gameboy: &Gameboy) {
+        if self.window.is_key_pressed(Key::M, KeyRepeat::No) {
+            self.carte ^= 0x9800 ^ 0x9C00;
+        }
+        if self.window.is_key_pressed(Key::C, KeyRepeat::No) {
+            self.cadres = !self.cadres;
+        }
+        let image = gameboy.get_image_carte(self.carte, self.cadres);
+        if self.window.is_key_pressed(Key::S, KeyRepeat::No) {
+            let chemin = format!("carte_{:04X}.png", self.carte);
+            match enregistrer_png(&image, &chemin) {
+                Ok(()) => eprintln!("carte enregistrée dans {}", chemin),
+                Err(erreur) => eprintln!("{} : {}", chemin, erreur),
+            }
+        }
+        let role = match (self.carte == gameboy.get_carte(false), self.carte == gameboy.get_carte(true)) {
+            (true, true) => " (fond et fenêtre)",
+            (true, false) => " (fond)",
+            (false, true) => " (fenêtre)",
+            (false, false) => "",
+        };
+        self.window.set_title(&format!("Carte {:04X}{}", self.carte, role));
+        convertir_image(&image, &mut self.buffer);
+        self.window.update_with_buffer(&self.buffer, image.largeur, image.hauteur).unwrap();
+    }
+}
+
+// Fenêtre des sprites de l'OAM, agrandis : les sprites écartés par la limite de 10 par ligne sont
+// encadrés en rouge, le titre décode l'entrée sous la souris.
+struct FenetreSprites {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl FenetreSprites {
+    fn new() -> FenetreSprites {
+        let options = WindowOptions {
+            scale: Scale::X4,
+            ..Default::default()
+        };
+        FenetreSprites {
+            window: Window::new("Sprites", 96, 100, options).unwrap(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn actualiser(&mut self, gameboy: &Gameboy) {
+        let image = gameboy.get_image_sprites();
+        let entree = self
+            .window
+            .get_mouse_pos(MouseMode::Discard)
+            .and_then(|(x, y)| EntreeOam::sous(x as usize, y as usize));
+        let titre = match entree {
+            Some(index) => format!("Sprites - {}", gameboy.get_entrees_oam()[index]),
+            None => "Sprites".to_string(),
+        };
+        self.window.set_title(&titre);
+        convertir_image(&image, &mut self.buffer);
+        self.window.update_with_buffer(&self.buffer, image.largeur, image.hauteur).unwrap();
+    }
+}
+
+// Fenêtre des palettes : BGP et les palettes CGB du fond à gauche, OBP0, OBP1 et les palettes
+// CGB des sprites à droite. Le titre donne la valeur brute de la couleur sous la souris, que la
+// commande `palette` du débogueur permet de modifier.
+struct FenetrePalettes {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl FenetrePalettes {
+    fn new() -> FenetrePalettes {
+        let options = WindowOptions {
+            scale: Scale::X4,
+            ..Default::default()
+        };
+        FenetrePalettes {
+            window: Window::new("Palettes", 100, 100, options).unwrap(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn actualiser(&mut self, gameboy: &Gameboy) {
+        let image = gameboy.get_image_palettes();
+        let couleur = self
+            .window
+            .get_mouse_pos(MouseMode::Discard)
+            .and_then(|(x, y)| CouleurPalette::sous(x as usize, y as usize));
+        let titre = match couleur {
+            Some(couleur) => {
+                let valeur = gameboy.get_valeur_couleur(couleur);
+                match couleur.source {
+                    SourcePalette::Bgp | SourcePalette::Obp0 | SourcePalette::Obp1 => format!(
+                        "Palettes - {} couleur {} : nuance {}",
+                        couleur.source, couleur.index, valeur
+                    ),
+                    _ => format!(
+                        "Palettes - {} couleur {} : ${:04X}",
+                        couleur.source, couleur.index, valeur
+                    ),
+                }
+            }
+            None => "Palettes".to_string(),
+        };
+        self.window.set_title(&titre);
+        convertir_image(&image, &mut self.buffer);
+        self.window.update_with_buffer(&self.buffer, image.largeur, image.hauteur).unwrap();
+    }
+}
+
+// Lit les commandes du débogueur sur l'entrée standard tant que l'émulation est en pause.
+// Renvoie false si l'émulateur doit être quitté.
+fn deboguer(debogueur: &mut Debogueur, gameboy: &mut Gameboy) -> bool {
+    eprintln!("{}", debogueur.invite(gameboy));
+    let stdin = io::stdin();
+    loop {
+        eprint!("> ");
+        io::stderr().flush().unwrap();
+        let mut commande = String::new();
+        if stdin.lock().read_line(&mut commande).unwrap() == 0 {
+            return false;
+        }
+        match debogueur.executer(gameboy, &commande) {
+            Reponse::Pause(texte) => {
+                if !texte.is_empty() {
+                    eprintln!("{}", texte);
+                }
+            }
+            Reponse::Reprise => return true,
+            Reponse::Quitter => return false,
+        }
+    }
+}
+
 fn main() {
     let mut rom_path = String::from("");
+    let mut watches: Vec<String> = Vec::new();
+    let mut debug = false;
+    let mut trace = false;
+    let mut breakpoints_ldbb = false;
+    let mut messages_lddd = false;
+    let mut strict = false;
+    let mut fifo = false;
+    let mut sans_limite_sprites = false;
+    let mut bug_stat = false;
+    let mut corruption_oam = false;
+    let mut carreaux = false;
+    let mut carte = false;
+    let mut sprites = false;
+    let mut palettes = false;
     {
         let mut arg_parser = ArgumentParser::new();
         arg_parser.set_description("Emulateur de Gameboy");
@@ -25,6 +293,48 @@ fn main() {
             .refer(&mut rom_path)
             .add_argument("rom", Store, "Chemin")
             .required();
+        arg_parser
+            .refer(&mut watches)
+            .add_option(&["--watch"], Collect, "Surveille les accès à ADDR ou DEBUT-FIN (hexadécimal)");
+        arg_parser
+            .refer(&mut debug)
+            .add_option(&["--debug"], StoreTrue, "Démarre en pause dans le débogueur");
+        arg_parser
+            .refer(&mut trace)
+            .add_option(&["--trace"], StoreTrue, "Affiche chaque instruction exécutée");
+        arg_parser
+            .refer(&mut breakpoints_ldbb)
+            .add_option(&["--ld-bb"], StoreTrue, "LD B, B met l'émulation en pause dans le débogueur");
+        arg_parser
+            .refer(&mut messages_lddd)
+            .add_option(&["--ld-dd"], StoreTrue, "Affiche les messages de debug LD D, D (format no$gmb)");
+        arg_parser
+            .refer(&mut strict)
+            .add_option(&["--strict"], StoreTrue, "Signale les comportements illégaux du programme");
+        arg_parser
+            .refer(&mut fifo)
+            .add_option(&["--fifo"], StoreTrue, "Rendu au dot près (pixel FIFO), plus lent");
+        arg_parser
+            .refer(&mut sans_limite_sprites)
+            .add_option(&["--sans-limite-sprites"], StoreTrue, "Affiche plus de 10 sprites par ligne");
+        arg_parser
+            .refer(&mut bug_stat)
+            .add_option(&["--bug-stat"], StoreTrue, "Reproduit le bug d'écriture dans STAT du DMG");
+        arg_parser
+            .refer(&mut corruption_oam)
+            .add_option(&["--corruption-oam"], StoreTrue, "Reproduit la corruption de l'OAM du DMG");
+        arg_parser
+            .refer(&mut carreaux)
+            .add_option(&["--carreaux"], StoreTrue, "Ouvre la fenêtre des carreaux de la VRAM");
+        arg_parser
+            .refer(&mut carte)
+            .add_option(&["--carte"], StoreTrue, "Ouvre la fenêtre de la carte du fond");
+        arg_parser
+            .refer(&mut sprites)
+            .add_option(&["--sprites"], StoreTrue, "Ouvre la fenêtre des sprites de l'OAM");
+        arg_parser
+            .refer(&mut palettes)
+            .add_option(&["--palettes"], StoreTrue, "Ouvre la fenêtre des palettes DMG et CGB");
         arg_parser.parse_args_or_exit();
     }
 
@@ -39,9 +349,37 @@ fn main() {
     file.read_to_end(&mut rom).unwrap();
 
     let mut gameboy = Gameboy::new(rom);
+    // Les symboles RGBDS sont cherchés à côté de la ROM : jeu.gb -> jeu.sym
+    if let Ok(symboles) = fs::read_to_string(Path::new(&rom_path).with_extension("sym")) {
+        gameboy.charger_symboles(&symboles);
+    }
+    gameboy.set_breakpoints_logiciels(breakpoints_ldbb);
+    gameboy.set_messages_debug(messages_lddd);
+    gameboy.set_strict(strict);
+    if fifo {
+        gameboy.set_rendu(Rendu::Fifo);
+    }
+    gameboy.set_limite_sprites(!sans_limite_sprites);
+    gameboy.set_bug_stat(bug_stat);
+    gameboy.set_corruption_oam(corruption_oam);
+    let mut debogueur = Debogueur::new();
+    if debug {
+        debogueur.pauser();
+    }
+    let debogueur_actif = debug || breakpoints_ldbb;
+    let mut verrou_signale = false;
+    for watch in &watches {
+        // Même sortie qu'argparse pour un argument invalide.
+        let Some(range) = parse_plage(watch) else {
+            eprintln!("watch: plage invalide {}", watch);
+            process::exit(2);
+        };
+        gameboy.add_hook(HookKind::Read, range.clone(), None, afficher_acces);
+        gameboy.add_hook(HookKind::Write, range, None, afficher_acces);
+    }
 
     let mut window = Window::new(
-        &format!("Gameboy"),
+        "Gameboy",
         gameboy.get_screen_dimension()[1],
         gameboy.get_screen_dimension()[0],
         window_options,
@@ -52,9 +390,61 @@ fn main() {
         .update_with_buffer(window_buffer.as_slice(), gameboy.get_screen_dimension()[1], gameboy.get_screen_dimension()[0])
         .unwrap();
 
+    let mut fenetre_carreaux = carreaux.then(FenetreCarreaux::new);
+    let mut fenetre_carte = carte.then(|| FenetreCarte::new(&gameboy));
+    let mut fenetre_sprites = sprites.then(FenetreSprites::new);
+    let mut fenetre_palettes = palettes.then(FenetrePalettes::new);
+    let mut ecran_etait_allume = gameboy.is_ecran_allume();
+    let mut derniere_image = Instant::now();
     while window.is_open() {
-        gameboy.step();
-        if gameboy.has_screen_updated() {
+        if debogueur_actif {
+            debogueur.avant_step(&gameboy);
+            if debogueur.en_pause() && !deboguer(&mut debogueur, &mut gameboy) {
+                break;
+            }
+        }
+        if trace && !gameboy.is_halted() {
+            eprintln!("{}", gameboy.trace());
+        }
+        if panic::catch_unwind(AssertUnwindSafe(|| gameboy.step())).is_err() {
+            eprintln!("{}", gameboy.rapport_crash());
+            process::exit(1);
+        }
+        if debogueur_actif {
+            debogueur.apres_step(&mut gameboy);
+        }
+        if let Status::LockedUp { pc, opcode } = gameboy.get_status() {
+            if !verrou_signale {
+                verrou_signale = true;
+                eprintln!(
+                    "cpu bloqué par l'op code invalide ${:02X} à {}",
+                    opcode,
+                    gameboy.formater_position(pc)
+                );
+            }
+            if debogueur_actif {
+                debogueur.pauser();
+                continue;
+            }
+            // Comme sur le matériel, l'image reste figée jusqu'à la fermeture de la fenêtre.
+            while window.is_open() {
+                window.update();
+                thread::sleep(Duration::from_millis(16));
+            }
+            break;
+        }
+        for avertissement in gameboy.take_avertissements() {
+            eprintln!(
+                "strict: {} : {}",
+                gameboy.formater_position_bank(avertissement.bank, avertissement.pc),
+                avertissement.diagnostic
+            );
+        }
+        // Écran éteint : l'image blanche est affichée une fois, puis la fenêtre est seulement
+        // rafraîchie pour rester réactive, faute de trames.
+        let ecran_allume = gameboy.is_ecran_allume();
+        let mut rafraichie = true;
+        if gameboy.has_screen_updated() || (ecran_etait_allume && !ecran_allume) {
             for (i, pixel) in gameboy.get_screen_data().iter().enumerate() {
                 let r = u32::from(pixel.r) << 16;
                 let g = u32::from(pixel.g) << 8;
@@ -65,6 +455,32 @@ fn main() {
             window
                 .update_with_buffer(window_buffer.as_slice(), gameboy.get_screen_dimension()[1], gameboy.get_screen_dimension()[0])
                 .unwrap();
+            derniere_image = Instant::now();
+        } else if !ecran_allume && derniere_image.elapsed() >= Duration::from_millis(16) {
+            window.update();
+            derniere_image = Instant::now();
+        } else {
+            rafraichie = false;
+        }
+        ecran_etait_allume = ecran_allume;
+        // Les fenêtres de débogage suivent le rythme de l'écran.
+        if rafraichie {
+            if let Some(fenetre) = &mut fenetre_carreaux {
+                fenetre.actualiser(&gameboy);
+            }
+            fenetre_carreaux = fenetre_carreaux.filter(|fenetre| fenetre.window.is_open());
+            if let Some(fenetre) = &mut fenetre_carte {
+                fenetre.actualiser(&gameboy);
+            }
+            fenetre_carte = fenetre_carte.filter(|fenetre| fenetre.window.is_open());
+            if let Some(fenetre) = &mut fenetre_sprites {
+                fenetre.actualiser(&gameboy);
+            }
+            fenetre_sprites = fenetre_sprites.filter(|fenetre| fenetre.window.is_open());
+            if let Some(fenetre) = &mut fenetre_palettes {
+                fenetre.actualiser(&gameboy);
+            }
+            fenetre_palettes = fenetre_palettes.filter(|fenetre| fenetre.window.is_open());
         }
         if gameboy.can_take_input() {
             for (physical_key, gameboy_button) in &KEY_MAPPINGS {
@@ -3,12 +3,32 @@ pub trait Memoire {
 
     fn set_octet(&mut self, addr: u16, value: u8);
 
-    fn get_mot(&self, addr: u16) -> u16 {
-        u16::from(self.get_octet(addr)) | (u16::from(self.get_octet(addr + 1)) << 8)
+    // Lecture d'un op code par le CPU, distincte d'une lecture de données.
+    fn get_op_code(&self, addr: u16) -> u8 {
+        self.get_octet(addr)
     }
+}
 
-    fn set_mot(&mut self, addr: u16, value: u16) {
-        self.set_octet(addr, (value & 0xFF) as u8);
-        self.set_octet(addr + 1, (value >> 8) as u8)
+// Bus vu par le CPU : chaque accès mémoire fait avancer le reste du système d'un cycle machine.
+pub trait Bus: Memoire {
+    fn tick(&mut self, cycles: u32);
+
+    // Accès internes du CPU (IF, IE, messages de debug) : ni hooks, ni contrôles du mode strict.
+    fn lire_interne(&self, addr: u16) -> u8 {
+        self.get_octet(addr)
+    }
+
+    fn ecrire_interne(&mut self, addr: u16, value: u8) {
+        self.set_octet(addr, value);
+    }
+
+    // Adresse présentée sur le bus sans lecture ni écriture, par un INC/DEC 16 bits.
+    fn placer_adresse(&mut self, _addr: u16) {}
+
+    // Fait avancer le système jusqu'à ce qu'une interruption autorisée soit demandée, au plus
+    // `limite` cycles. Renvoie les cycles écoulés, par cycles machine entiers.
+    fn attendre_interruption(&mut self, _limite: u32) -> u32 {
+        self.tick(4);
+        4
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,15 @@
+use std::cell::RefCell;
+
 use crate::cartouches::Cartouche;
-use crate::joypad::Joypad;
-use crate::memoire::Memoire;
-use crate::ppu::Ppu;
+use crate::empreinte::Empreinte;
+use crate::hooks::{HookEvent, HookKind, Hooks};
+use crate::joypad::{Joypad, JoypadKey};
+use crate::memoire::{Bus, Memoire};
+use crate::planificateur::{Evenement, Planificateur};
+use crate::ppu::visualiseur::{CouleurPalette, SourcePalette};
+use crate::ppu::{Ppu, DOTS_OAM};
+use crate::serie::Serie;
+use crate::strict::{Diagnostic, Strict};
 use crate::timer::timer::Timer;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
@@ -15,6 +23,7 @@ pub enum InterruptFlag {
     VBlank = 0b0000_0001,
     LCDStat = 0b0000_0010,
     Timer = 0b0000_0100,
+    Serial = 0b0000_1000,
     Joypad = 0b0001_0000,
     None = 0b0000_0000,
 }
@@ -22,36 +31,62 @@ pub enum InterruptFlag {
 const HRAM_SIZE: usize = 0x7F;
 const WRAM_SIZE: usize = 0x8000;
 const WRAM_BANK_SIZE: usize = 0x1000;
+// Durée d'une DMA OAM en cycles d'horloge : un octet par cycle machine.
+const DUREE_DMA: u64 = 0xA0 * 4;
 
 pub struct Mmu {
     pub cartouche: Box<dyn Cartouche>,
     pub ppu: Ppu,
     pub joypad: Joypad,
+    pub serie: Serie,
     timer: Timer,
+    planificateur: Planificateur,
+    // Instant jusqu'auquel le PPU a été exécuté, pour le rendu FIFO.
+    synchro_ppu: u64,
+    // Cartouche compatible Gameboy Color
+    pub cgb: bool,
     vitesse: Vitesse,
     prepare_vitesse_switch: bool,
+    // Page source de la DMA OAM en cours, copiée dans l'OAM à son échéance.
+    dma: Option<u8>,
     hram: [u8; HRAM_SIZE],
     wram: [u8; WRAM_SIZE],
     wram_bank: usize,
     interruptions_asserted: u8,
     interruptions_enabled: u8,
+    pub hooks: RefCell<Hooks>,
+    // Diagnostics du mode strict, None lorsqu'il est désactivé.
+    pub strict: Option<RefCell<Strict>>,
+    #[cfg(feature = "profilage")]
+    pub chronometre: crate::profilage::Chronometre,
 }
 
 impl Mmu {
     pub fn new(cartouche: Box<dyn Cartouche>) -> Mmu {
+        let cgb = cartouche.get_octet(0x0143) & 0x80 != 0x00;
         let mut mmu = Mmu {
             cartouche,
+            cgb,
             ppu: Ppu::new(),
             joypad: Joypad::new(),
+            serie: Serie::new(),
             timer: Timer::new(),
+            planificateur: Planificateur::new(),
+            synchro_ppu: 0,
             vitesse: Vitesse::Normal,
             prepare_vitesse_switch: false,
+            dma: None,
             hram: [0x00; HRAM_SIZE],
             wram: [0x00; WRAM_SIZE],
             wram_bank: 0x01,
             interruptions_asserted: InterruptFlag::None as u8,
             interruptions_enabled: 0x00,
+            hooks: RefCell::new(Hooks::new()),
+            strict: None,
+            #[cfg(feature = "profilage")]
+            chronometre: crate::profilage::Chronometre::new(),
         };
+        mmu.ppu.cgb = cgb;
 
         mmu.set_octet(0xFF05, 0x00);
         mmu.set_octet(0xFF06, 0x00);
@@ -83,42 +118,294 @@ impl Mmu {
         mmu.set_octet(0xFF49, 0xFF);
         mmu.set_octet(0xFF4A, 0x00);
         mmu.set_octet(0xFF4B, 0x00);
+        // La boot ROM laisse l'écran allumé : la première trame du jeu est affichée.
+        mmu.ppu.trame_masquee = false;
         mmu
     }
 
+    // Le changement de vitesse est armé par KEY1 et effectué par l'instruction STOP.
+    pub fn is_vitesse_switch_prepare(&self) -> bool {
+        self.cgb && self.prepare_vitesse_switch
+    }
+
+    // Les événements du PPU sont datés en cycles du CPU : ceux qui restent avant le prochain
+    // changement de mode doublent ou diminuent de moitié avec la vitesse.
     pub fn perform_vitesse_switch(&mut self) {
-        if self.prepare_vitesse_switch {
-            self.vitesse = if self.vitesse == Vitesse::Double {
-                Vitesse::Normal
-            } else {
-                Vitesse::Double
+        let ancienne = self.vitesse as u64;
+        self.vitesse = if self.vitesse == Vitesse::Double {
+            Vitesse::Normal
+        } else {
+            Vitesse::Double
+        };
+        self.prepare_vitesse_switch = false;
+        if let Some(echeance) = self.planificateur.get_echeance(Evenement::Ppu) {
+            let maintenant = self.planificateur.maintenant();
+            let restants = echeance.saturating_sub(maintenant) / ancienne * self.vitesse as u64;
+            self.planificateur.programmer(Evenement::Ppu, maintenant + restants);
+        }
+    }
+
+    // État de la console hors CPU. Les hooks et le mode strict, propres au débogage, n'en font pas partie.
+    pub fn empreinte(&self, empreinte: &mut Empreinte) {
+        let maintenant = self.planificateur.maintenant();
+        self.planificateur.empreinte(empreinte);
+        self.timer.empreinte(empreinte, maintenant);
+        self.ppu.empreinte(empreinte);
+        self.joypad.empreinte(empreinte);
+        self.serie.empreinte(empreinte);
+        self.cartouche.empreinte(empreinte);
+        empreinte.ecrire_bool(self.cgb);
+        empreinte.ecrire_u8(self.vitesse as u8);
+        empreinte.ecrire_bool(self.prepare_vitesse_switch);
+        empreinte.ecrire_bool(self.dma.is_some());
+        empreinte.ecrire_u8(self.dma.unwrap_or(0x00));
+        empreinte.ecrire(&self.hram);
+        empreinte.ecrire(&self.wram);
+        empreinte.ecrire_u8(self.wram_bank as u8);
+        empreinte.ecrire_u8(self.interruptions_asserted);
+        empreinte.ecrire_u8(self.interruptions_enabled);
+    }
+
+    // DIV est remis à zéro à l'exécution de STOP.
+    pub fn reset_div(&mut self) {
+        self.set_octet_timer(0xFF04, 0x00);
+    }
+
+    fn set_octet_timer(&mut self, addr: u16, value: u8) {
+        self.timer.set_octet(addr, value, self.planificateur.maintenant());
+        match self.timer.get_echeance() {
+            Some(echeance) => self.planificateur.programmer(Evenement::Timer, echeance),
+            None => self.planificateur.annuler(Evenement::Timer),
+        }
+    }
+
+    fn set_lcd_control(&mut self, value: u8) {
+        let allume = self.ppu.is_allume();
+        self.ppu.set_octet(0xFF40, value);
+        match (allume, self.ppu.is_allume()) {
+            (false, true) => self.programmer_ppu(self.planificateur.maintenant(), 1),
+            (true, false) => self.planificateur.annuler(Evenement::Ppu),
+            _ => {}
+        }
+    }
+
+    // L'interruption du joypad est demandée dès l'appui, entre deux steps du CPU.
+    pub fn keydown(&mut self, key: JoypadKey) {
+        self.joypad.keydown(key);
+        self.interruptions_asserted |= InterruptFlag::Joypad as u8;
+    }
+
+    // Une écriture dans SC démarre ou interrompt le transfert.
+    fn set_controle_serie(&mut self, value: u8) {
+        self.serie.set_octet(0xFF02, value);
+        self.serie.demarrer_transfert();
+        self.programmer_serie(self.planificateur.maintenant());
+    }
+
+    // Chaque bit transféré est un événement.
+    fn programmer_serie(&mut self, depuis: u64) {
+        match self.serie.get_duree_bit() {
+            Some(duree) => self.planificateur.programmer(Evenement::Serie, depuis + u64::from(duree)),
+            None => self.planificateur.annuler(Evenement::Serie),
+        }
+    }
+
+    // Pendant la DMA, le CPU ne peut plus accéder à l'OAM.
+    fn is_accessible(&self, addr: u16) -> bool {
+        self.ppu.is_accessible(addr) && !(self.dma.is_some() && matches!(addr, 0xFE00..=0xFE9F))
+    }
+
+    // Les pages E0-FF ne sont pas sur le bus de la DMA : elles lisent l'écho de la WRAM en C0-DF.
+    fn demarrer_dma(&mut self, page: u8) {
+        let page = if page >= 0xE0 { page - 0x20 } else { page };
+        self.dma = Some(page);
+        let echeance = self.planificateur.maintenant() + DUREE_DMA;
+        self.planificateur.programmer(Evenement::Dma, echeance);
+    }
+
+    // La copie est faite d'un bloc à l'échéance : l'OAM n'est pas accessible au CPU entre-temps.
+    fn terminer_dma(&mut self, echeance: u64) {
+        let Some(page) = self.dma.take() else {
+            return;
+        };
+        self.synchroniser_ppu(echeance);
+        let base = u16::from(page) << 8;
+        for i in 0..0xA0 {
+            let value = self.lire_octet(base + i);
+            self.ppu.set_octet(0xFE00 + i, value);
+        }
+    }
+
+    // En double vitesse, un dot dure deux cycles du CPU.
+    fn programmer_ppu(&mut self, depuis: u64, dots: u32) {
+        let cycles = u64::from(dots) * self.vitesse as u64;
+        self.planificateur.programmer(Evenement::Ppu, depuis + cycles);
+    }
+
+    // Avec le rendu FIFO, le mode 3 est exécuté jusqu'à l'instant donné avant chaque écriture qui
+    // peut modifier l'image, et avant le changement de mode.
+    fn synchroniser_ppu(&mut self, maintenant: u64) {
+        if !self.ppu.is_transfert_en_cours() {
+            return;
+        }
+        let dots = (maintenant - self.synchro_ppu) / self.vitesse as u64;
+        self.ppu.avancer_transfert(dots);
+        self.synchro_ppu += dots * self.vitesse as u64;
+    }
+
+    // Les cycles sont comptés à la fréquence du CPU. Seuls les événements échus font travailler
+    // les périphériques, dans l'ordre de leurs échéances.
+    pub fn run_cycles(&mut self, cycles: u32) {
+        self.planificateur.avancer(cycles);
+        while let Some((evenement, echeance)) = self.planificateur.prochain_echu() {
+            #[cfg(feature = "profilage")]
+            let debut = std::time::Instant::now();
+            match evenement {
+                Evenement::Timer => {
+                    self.timer.recharger(echeance);
+                    self.interruptions_asserted |= self.timer.interrupt;
+                    self.timer.interrupt = InterruptFlag::None as u8;
+                    if let Some(echeance) = self.timer.get_echeance() {
+                        self.planificateur.programmer(Evenement::Timer, echeance);
+                    }
+                }
+                Evenement::Ppu => {
+                    self.synchroniser_ppu(echeance);
+                    let dots = self.ppu.evenement();
+                    self.synchro_ppu = echeance;
+                    self.interruptions_asserted |= self.ppu.interrupt;
+                    self.ppu.interrupt = InterruptFlag::None as u8;
+                    self.programmer_ppu(echeance, dots);
+                }
+                Evenement::Serie => {
+                    self.serie.transferer_bit();
+                    self.interruptions_asserted |= self.serie.interrupt;
+                    self.serie.interrupt = InterruptFlag::None as u8;
+                    self.programmer_serie(echeance);
+                }
+                Evenement::Dma => self.terminer_dma(echeance),
             }
+            #[cfg(feature = "profilage")]
+            self.chronometre.ajouter_evenement(evenement, debut.elapsed());
         }
-        self.prepare_vitesse_switch = false;
     }
 
-    pub fn run_cycles(&mut self, cycles: u32) -> u32 {
-        let cpu_divider = self.vitesse as u32;
-        let ppu_cycles = cycles / cpu_divider ;
-        let cpu_cycles = cycles + cpu_divider;
+    // Modifie une couleur par les registres du PPU, comme le ferait le programme, pour essayer une
+    // palette en cours de jeu.
+    pub fn set_valeur_couleur(&mut self, couleur: CouleurPalette, valeur: u16) {
+        let registre = match couleur.source {
+            SourcePalette::Bgp => 0xFF47,
+            SourcePalette::Obp0 => 0xFF48,
+            SourcePalette::Obp1 => 0xFF49,
+            SourcePalette::Fond(palette) => return self.ecrire_couleur_cgb(0xFF68, palette, couleur.index, valeur),
+            SourcePalette::Sprites(palette) => return self.ecrire_couleur_cgb(0xFF6A, palette, couleur.index, valeur),
+        };
+        let decalage = 2 * couleur.index;
+        let nuances = self.lire_octet(registre) & !(0x03 << decalage);
+        self.ecrire_octet(registre, nuances | ((valeur as u8 & 0x03) << decalage));
+    }
 
-        self.timer.run_cycles(cpu_cycles);
-        self.interruptions_asserted |= self.timer.interrupt;
-        self.timer.interrupt = InterruptFlag::None as u8;
+    // Écrit une couleur RGB555 par BCPS/BCPD ou OCPS/OCPD : deux octets en petit-boutiste à l'index
+    // palette * 8 + couleur * 2, puis l'index du programme est restauré. Ces registres n'existent
+    // pas sur DMG, où l'écriture est ignorée.
+    fn ecrire_couleur_cgb(&mut self, registre_index: u16, palette: usize, index: usize, valeur: u16) {
+        let index_programme = self.lire_octet(registre_index);
+        self.ecrire_octet(registre_index, 0x80 | (palette * 8 + index * 2) as u8);
+        for octet in (valeur & 0x7FFF).to_le_bytes() {
+            self.ecrire_octet(registre_index + 1, octet);
+        }
+        self.ecrire_octet(registre_index, index_programme);
+    }
 
-        self.interruptions_asserted |= self.joypad.interrupt;
-        self.joypad.interrupt = InterruptFlag::None as u8;
+    // Banque de la cartouche projetée sur l'adresse, pour les zones qui en ont une.
+    pub fn get_bank(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x4000..=0x7FFF => Some(self.cartouche.rom_bank()),
+            0xA000..=0xBFFF => Some(self.cartouche.ram_bank()),
+            _ => None,
+        }
+    }
 
-        self.ppu.run_cycles(ppu_cycles);
-        self.interruptions_asserted |= self.ppu.interrupt;
-        self.ppu.interrupt = InterruptFlag::None as u8;
+    fn declencher_hooks(&self, kind: HookKind, addr: u16, value: u8) {
+        let mut hooks = self.hooks.borrow_mut();
+        if hooks.is_empty() {
+            return;
+        }
+        let event = HookEvent {
+            kind,
+            addr,
+            bank: self.get_bank(addr),
+            value,
+        };
+        hooks.declencher(&event);
+    }
 
-        ppu_cycles
+    fn get_wram_index(&self, addr: u16) -> usize {
+        match addr & 0x1FFF {
+            0x0000..=0x0FFF => addr as usize & 0x0FFF,
+            _ => (addr as usize & 0x0FFF) + WRAM_BANK_SIZE * self.wram_bank,
+        }
     }
-}
 
-impl Memoire for Mmu {
-    fn get_octet(&self, addr: u16) -> u8 {
+    pub fn verifier_pile(&self, sp: u16) {
+        if let Some(strict) = &self.strict {
+            if !matches!(sp, 0xC001..=0xE000 | 0xFF81..=0xFFFF) {
+                strict.borrow_mut().signaler(Diagnostic::PileHorsRam { sp });
+            }
+        }
+    }
+
+    fn verifier_acces_ppu(&self, strict: &mut Strict, addr: u16) {
+        if self.ppu.is_accessible(addr) {
+            return;
+        }
+        let mode = self.ppu.get_mode();
+        match addr {
+            0x8000..=0x9FFF => strict.signaler(Diagnostic::AccesVram { addr, mode }),
+            _ => strict.signaler(Diagnostic::AccesOam { addr, mode }),
+        }
+    }
+
+    fn verifier_lecture(&self, addr: u16) {
+        let Some(strict) = &self.strict else {
+            return;
+        };
+        let mut strict = strict.borrow_mut();
+        match addr {
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.verifier_acces_ppu(&mut strict, addr),
+            0xA000..=0xBFFF if !self.cartouche.is_ram_accessible() => {
+                strict.signaler(Diagnostic::LectureRamDesactivee { addr })
+            }
+            0xC000..=0xFDFF if !strict.is_wram_init(self.get_wram_index(addr)) => {
+                strict.signaler(Diagnostic::LectureNonInitialisee { addr })
+            }
+            0xFF80..=0xFFFE if !strict.is_hram_init(addr as usize - 0xFF80) => {
+                strict.signaler(Diagnostic::LectureNonInitialisee { addr })
+            }
+            0xFF4C | 0xFF4E => strict.signaler(Diagnostic::RegistreInexistant { addr }),
+            _ => {}
+        }
+    }
+
+    fn verifier_ecriture(&self, addr: u16, value: u8) {
+        let Some(strict) = &self.strict else {
+            return;
+        };
+        let mut strict = strict.borrow_mut();
+        match addr {
+            0x0000..=0x7FFF if self.cartouche.is_rom_only() => {
+                strict.signaler(Diagnostic::EcritureRom { addr, value })
+            }
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.verifier_acces_ppu(&mut strict, addr),
+            0xC000..=0xFDFF => strict.set_wram_init(self.get_wram_index(addr)),
+            0xFF4C | 0xFF4E => strict.signaler(Diagnostic::RegistreInexistant { addr }),
+            0xFF80..=0xFFFE => strict.set_hram_init(addr as usize - 0xFF80),
+            _ => {}
+        }
+    }
+
+    // Lecture sans déclencher les hooks ni les restrictions d'accès du PPU (débogueur, DMA).
+    pub fn lire_octet(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x7FFF => self.cartouche.get_octet(addr),
                         0x8000..=0x9FFF => self.ppu.get_octet(addr),
@@ -142,7 +429,8 @@ impl Memoire for Mmu {
             0xFF00..=0xFF7F => {
                 match addr {
                     0xFF00 => self.joypad.get_octet(addr),
-                    0xFF04..=0xFF07 => self.timer.get_octet(addr),
+                    0xFF01..=0xFF02 => self.serie.get_octet(addr),
+                    0xFF04..=0xFF07 => self.timer.get_octet(addr, self.planificateur.maintenant()),
                     0xFF0F => self.interruptions_asserted,
                     0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.get_octet(addr),
                     0xFF4D => {
@@ -156,7 +444,7 @@ impl Memoire for Mmu {
                         };
                         current_vitesse_bit | prepare_switch_bit
                     }
-                    0xFF4F => self.ppu.get_octet(addr),
+                    0xFF4C | 0xFF4E..=0xFF4F => self.ppu.get_octet(addr),
                     0xFF68..=0xFF6B => self.ppu.get_octet(addr),
                     _ => 0x00,
                 }
@@ -167,7 +455,11 @@ impl Memoire for Mmu {
     }
 
 
-    fn set_octet(&mut self, addr: u16, value: u8) {
+    // Écriture sans déclencher les hooks ni les restrictions d'accès du PPU (débogueur, DMA).
+    pub fn ecrire_octet(&mut self, addr: u16, value: u8) {
+        if let 0x8000..=0x9FFF | 0xFE00..=0xFE9F | 0xFF40..=0xFF4F | 0xFF68..=0xFF6B = addr {
+            self.synchroniser_ppu(self.planificateur.maintenant());
+        }
         match addr {
             0x0000..=0x7FFF => self.cartouche.set_octet(addr, value),
             0x8000..=0x9FFF => self.ppu.set_octet(addr, value),
@@ -191,24 +483,23 @@ impl Memoire for Mmu {
             0xFF00..=0xFF7F => {
                 match addr {
                     0xFF00 => self.joypad.set_octet(addr, value),
-                    0xFF04..=0xFF07 => self.timer.set_octet(addr, value),
+                    0xFF01 => self.serie.set_octet(addr, value),
+                    0xFF02 => self.set_controle_serie(value),
+                    0xFF04..=0xFF07 => self.set_octet_timer(addr, value),
                     0xFF0F => self.interruptions_asserted = value,
-                    0xFF40..=0xFF45 => self.ppu.set_octet(addr, value),
-                    0xFF46 => {
-                        assert!(
-                            value <= 0xF1,
-                        );
-                        let base = u16::from(value) << 8;
-                        for i in 0..0xA0 {
-                            let value = self.get_octet(base + i);
-                            self.set_octet(0xFE00 + i, value);
-                        }
+                    0xFF40 => self.set_lcd_control(value),
+                    0xFF41..=0xFF45 => {
+                        // STAT et LYC peuvent lever la ligne d'interruption STAT.
+                        self.ppu.set_octet(addr, value);
+                        self.interruptions_asserted |= self.ppu.interrupt;
+                        self.ppu.interrupt = InterruptFlag::None as u8;
                     }
+                    0xFF46 => self.demarrer_dma(value),
                     0xFF47..=0xFF4B => self.ppu.set_octet(addr, value),
                     0xFF4D => {
                         self.prepare_vitesse_switch = (value & 0b0000_0001) == 0b0000_0001;
                     }
-                    0xFF4F => self.ppu.set_octet(addr, value),
+                    0xFF4C | 0xFF4E..=0xFF4F => self.ppu.set_octet(addr, value),
                     0xFF68..=0xFF6B => self.ppu.set_octet(addr, value),
                     0xFF70 => {
                         self.wram_bank = match value & 0x07 {
@@ -223,4 +514,251 @@ impl Memoire for Mmu {
             0xFFFF => self.interruptions_enabled = value,
         }
     }
-}
\ No newline at end of file
+}
+
+impl Memoire for Mmu {
+    fn get_octet(&self, addr: u16) -> u8 {
+        #[cfg(feature = "profilage")]
+        let debut = self.chronometre.debut_acces();
+        self.verifier_lecture(addr);
+        let value = if self.is_accessible(addr) {
+            self.lire_octet(addr)
+        } else {
+            0xFF
+        };
+        self.declencher_hooks(HookKind::Read, addr, value);
+        #[cfg(feature = "profilage")]
+        self.chronometre.fin_acces(debut);
+        value
+    }
+
+    fn set_octet(&mut self, addr: u16, value: u8) {
+        #[cfg(feature = "profilage")]
+        let debut = self.chronometre.debut_acces();
+        self.verifier_ecriture(addr, value);
+        if self.is_accessible(addr) {
+            self.ecrire_octet(addr, value);
+        }
+        self.declencher_hooks(HookKind::Write, addr, value);
+        #[cfg(feature = "profilage")]
+        self.chronometre.fin_acces(debut);
+    }
+
+    fn get_op_code(&self, addr: u16) -> u8 {
+        #[cfg(feature = "profilage")]
+        let debut = self.chronometre.debut_acces();
+        let value = if self.is_accessible(addr) {
+            self.lire_octet(addr)
+        } else {
+            0xFF
+        };
+        self.declencher_hooks(HookKind::Execute, addr, value);
+        #[cfg(feature = "profilage")]
+        self.chronometre.fin_acces(debut);
+        value
+    }
+}
+
+impl Bus for Mmu {
+    fn tick(&mut self, cycles: u32) {
+        self.run_cycles(cycles);
+    }
+
+    fn lire_interne(&self, addr: u16) -> u8 {
+        self.lire_octet(addr)
+    }
+
+    fn ecrire_interne(&mut self, addr: u16, value: u8) {
+        self.ecrire_octet(addr, value);
+    }
+
+    fn placer_adresse(&mut self, addr: u16) {
+        let corruption = self.ppu.corruption_oam && !self.ppu.cgb && self.ppu.get_mode() == 2;
+        if !corruption || !matches!(addr, 0xFE00..=0xFEFF) {
+            return;
+        }
+        // Le mode 2 se termine à l'échéance de l'événement du PPU, DOTS_OAM dots après son début.
+        let Some(echeance) = self.planificateur.get_echeance(Evenement::Ppu) else {
+            return;
+        };
+        let restants = echeance.saturating_sub(self.planificateur.maintenant()) / self.vitesse as u64;
+        self.ppu.corrompre_oam(u64::from(DOTS_OAM).saturating_sub(restants) as usize / 4);
+    }
+
+    // Les interruptions ne sont demandées que par les événements du planificateur (et le joypad,
+    // entre deux steps) : le temps avance d'un événement à l'autre.
+    fn attendre_interruption(&mut self, limite: u32) -> u32 {
+        let mut cycles = 0;
+        loop {
+            let restants = limite.saturating_sub(cycles);
+            let attente = match self.planificateur.get_prochaine_echeance() {
+                Some(echeance) => {
+                    let attente = echeance.saturating_sub(self.planificateur.maintenant());
+                    // Arrondi au cycle machine supérieur
+                    (attente.max(1).div_ceil(4) * 4).min(u64::from(restants)) as u32
+                }
+                None => restants,
+            };
+            let attente = attente.max(4);
+            self.run_cycles(attente);
+            cycles += attente;
+            if self.interruptions_asserted & self.interruptions_enabled & 0x1F != 0 || cycles >= limite {
+                return cycles;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::cartouches;
+    use crate::memoire::{Bus, Memoire};
+    use crate::mmu::Mmu;
+    use crate::ppu::visualiseur::{CouleurPalette, SourcePalette};
+    use crate::strict::{Diagnostic, Strict};
+
+    // L'écran est allumé par Mmu::new : la ligne 0 commence au cycle 1, la ligne 1 au cycle 456.
+    fn creer_mmu() -> Mmu {
+        Mmu::new(cartouches::new(vec![0x00; 0x8000]))
+    }
+
+    // Horloge interne lente : un bit toutes les 512 cycles, l'interruption au huitième.
+    #[test]
+    fn serie_bit_par_bit() {
+        let mut mmu = creer_mmu();
+        mmu.set_octet(0xFF01, 0x81);
+        mmu.set_octet(0xFF02, 0x81);
+        mmu.run_cycles(511);
+        assert_eq!(mmu.get_octet(0xFF01), 0x81);
+        mmu.run_cycles(1);
+        assert_eq!(mmu.get_octet(0xFF01), 0x03);
+        mmu.run_cycles(6 * 512);
+        assert_eq!(mmu.get_octet(0xFF01), 0xFF);
+        assert_eq!(mmu.get_octet(0xFF02) & 0x80, 0x80);
+        assert_eq!(mmu.get_octet(0xFF0F) & 0x08, 0x00);
+        mmu.run_cycles(512);
+        assert_eq!(mmu.get_octet(0xFF02) & 0x80, 0x00);
+        assert_eq!(mmu.get_octet(0xFF0F) & 0x08, 0x08);
+        assert_eq!(mmu.serie.sortie, [0x81]);
+    }
+
+    // Les pages E0-FF de la DMA lisent la WRAM en C0-DF.
+    #[test]
+    fn dma_depuis_les_pages_hautes() {
+        let mut mmu = creer_mmu();
+        for (page, source) in [(0xE0, 0xC000), (0xFE, 0xDE00)] {
+            for i in 0..0xA0 {
+                mmu.ecrire_octet(source + i, i as u8 ^ page);
+            }
+            mmu.ecrire_octet(0xFF46, page);
+            mmu.run_cycles(0xA0 * 4);
+            assert!((0..0xA0).all(|i| mmu.lire_octet(0xFE00 + i) == i as u8 ^ page));
+        }
+    }
+
+    // Le CPU lit 0xFF et ses écritures sont ignorées : OAM en modes 2 et 3, VRAM en mode 3.
+    #[test]
+    fn acces_bloques_selon_le_mode() {
+        let mut mmu = creer_mmu();
+        mmu.ecrire_octet(0x8000, 0x12);
+        mmu.ecrire_octet(0xFE00, 0x34);
+
+        mmu.run_cycles(4);
+        assert_eq!(mmu.ppu.get_mode(), 2);
+        assert_eq!(mmu.get_octet(0x8000), 0x12);
+        assert_eq!(mmu.get_octet(0xFE00), 0xFF);
+        mmu.set_octet(0xFE00, 0x56);
+        assert_eq!(mmu.lire_octet(0xFE00), 0x34);
+
+        // Le mode 2 occupe les dots 0 à 79, le mode 3 commence au dot 80.
+        mmu.run_cycles(75);
+        assert_eq!(mmu.ppu.get_mode(), 2);
+        mmu.run_cycles(1);
+        assert_eq!(mmu.ppu.get_mode(), 3);
+        assert_eq!(mmu.get_octet(0x8000), 0xFF);
+        assert_eq!(mmu.get_op_code(0x8000), 0xFF);
+        mmu.set_octet(0x8000, 0x78);
+        assert_eq!(mmu.lire_octet(0x8000), 0x12);
+        assert_eq!(mmu.get_octet(0xFE00), 0xFF);
+
+        mmu.run_cycles(171);
+        assert_eq!(mmu.ppu.get_mode(), 3);
+        mmu.run_cycles(1);
+        assert_eq!(mmu.ppu.get_mode(), 0);
+        mmu.set_octet(0x8000, 0x78);
+        mmu.set_octet(0xFE00, 0x56);
+        assert_eq!(mmu.get_octet(0x8000), 0x78);
+        assert_eq!(mmu.get_octet(0xFE00), 0x56);
+    }
+
+    // Un INC/DEC 16 bits dans l'OAM corrompt la rangée lue à cet instant du mode 2 : la rangée 2
+    // après 8 dots.
+    #[test]
+    fn corruption_oam_pendant_le_mode_2() {
+        let mut mmu = creer_mmu();
+        mmu.ppu.corruption_oam = true;
+        for i in 0..0xA0 {
+            mmu.ecrire_octet(0xFE00 + i, i as u8);
+        }
+        mmu.run_cycles(456 + 8);
+        assert_eq!(mmu.ppu.get_octet(0xFF44), 1);
+        assert_eq!(mmu.ppu.get_mode(), 2);
+        mmu.placer_adresse(0xFE10);
+        let oam: Vec<u8> = (0..0xA0).map(|i| mmu.lire_octet(0xFE00 + i)).collect();
+        assert_eq!(&oam[..16], &(0..16).collect::<Vec<u8>>()[..]);
+        assert_eq!(&oam[18..24], &[10, 11, 12, 13, 14, 15]);
+        assert_eq!(&oam[24..], &(24..0xA0).collect::<Vec<u8>>()[..]);
+
+        // Hors de l'OAM, ou sans l'option, rien n'est corrompu.
+        let mut mmu = creer_mmu();
+        for i in 0..0xA0 {
+            mmu.ecrire_octet(0xFE00 + i, i as u8);
+        }
+        mmu.run_cycles(456 + 8);
+        mmu.placer_adresse(0xFE10);
+        mmu.ppu.corruption_oam = true;
+        mmu.placer_adresse(0xC000);
+        assert!((0..0xA0).all(|i| mmu.lire_octet(0xFE00 + i) == i as u8));
+    }
+
+    // Une couleur modifiée par le débogueur se relit par les registres du programme, dont l'index
+    // est préservé.
+    #[test]
+    fn modifier_une_couleur() {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0143] = 0x80;
+        let mut mmu = Mmu::new(cartouches::new(rom));
+        mmu.set_octet(0xFF68, 0x85);
+        mmu.set_octet(0xFF6A, 0x03);
+        mmu.set_valeur_couleur(CouleurPalette { source: SourcePalette::Fond(2), index: 1 }, 0x7FFF);
+        mmu.set_valeur_couleur(CouleurPalette { source: SourcePalette::Sprites(7), index: 3 }, 0x1234);
+        assert_eq!(mmu.get_octet(0xFF68), 0xC5);
+        assert_eq!(mmu.get_octet(0xFF6A), 0x43);
+        let mut lire = |registre_index: u16, index: u8| {
+            mmu.set_octet(registre_index, index);
+            mmu.get_octet(registre_index + 1)
+        };
+        assert_eq!([lire(0xFF68, 18), lire(0xFF68, 19)], [0xFF, 0x7F]);
+        assert_eq!([lire(0xFF6A, 62), lire(0xFF6A, 63)], [0x34, 0x12]);
+
+        mmu.set_octet(0xFF47, 0xE4);
+        mmu.set_valeur_couleur(CouleurPalette { source: SourcePalette::Bgp, index: 2 }, 0);
+        assert_eq!(mmu.get_octet(0xFF47), 0xC4);
+    }
+
+    // Les registres inexistants du PPU lisent 0xFF et ignorent les écritures, avec un avertissement.
+    #[test]
+    fn registre_inexistant_du_ppu() {
+        let mut mmu = creer_mmu();
+        mmu.strict = Some(RefCell::new(Strict::new()));
+        mmu.set_octet(0xFF4C, 0x12);
+        assert_eq!(mmu.get_octet(0xFF4C), 0xFF);
+        assert_eq!(mmu.get_octet(0xFF4E), 0xFF);
+        // Un même diagnostic n'est signalé qu'une fois par instruction.
+        let avertissements = mmu.strict.as_ref().unwrap().borrow_mut().take_avertissements();
+        assert_eq!(avertissements.len(), 1);
+        assert_eq!(avertissements[0].diagnostic, Diagnostic::RegistreInexistant { addr: 0xFF4C });
+    }
+}
@@ -0,0 +1,104 @@
+use crate::empreinte::Empreinte;
+
+// Événements des périphériques, datés en cycles d'horloge du CPU depuis la mise sous tension.
+// Entre deux événements, un périphérique n'a rien à faire : son état se déduit du temps écoulé.
+// Le son n'est pas émulé : il n'y a pas d'APU, donc pas d'événement pour son séquenceur de trames.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Evenement {
+    // Rechargement de TIMA avec TMA, un cycle machine après son dépassement.
+    Timer,
+    // Changement de mode du PPU : fin de l'OAM scan, fin du transfert, fin de ligne.
+    Ppu,
+    // Transfert d'un bit sur le port série.
+    Serie,
+    // Fin de la DMA OAM, 160 cycles machine après l'écriture dans FF46.
+    Dma,
+}
+
+// À échéance égale, les événements sont traités dans cet ordre.
+const EVENEMENTS: [Evenement; 4] = [
+    Evenement::Timer,
+    Evenement::Ppu,
+    Evenement::Serie,
+    Evenement::Dma,
+];
+
+const JAMAIS: u64 = u64::MAX;
+
+pub struct Planificateur {
+    maintenant: u64,
+    echeances: [u64; EVENEMENTS.len()],
+    // Échéance la plus proche, pour ne parcourir les événements que lorsque l'un d'eux est dû.
+    prochaine: u64,
+}
+
+impl Planificateur {
+    pub fn new() -> Planificateur {
+        Planificateur {
+            maintenant: 0,
+            echeances: [JAMAIS; EVENEMENTS.len()],
+            prochaine: JAMAIS,
+        }
+    }
+
+    pub fn maintenant(&self) -> u64 {
+        self.maintenant
+    }
+
+    pub fn avancer(&mut self, cycles: u32) {
+        self.maintenant += u64::from(cycles);
+    }
+
+    // Programme l'événement à un instant donné, en remplaçant son échéance précédente.
+    pub fn programmer(&mut self, evenement: Evenement, echeance: u64) {
+        self.echeances[evenement as usize] = echeance;
+        self.prochaine = self.prochaine.min(echeance);
+    }
+
+    pub fn annuler(&mut self, evenement: Evenement) {
+        self.echeances[evenement as usize] = JAMAIS;
+        self.calculer_prochaine();
+    }
+
+    pub fn get_echeance(&self, evenement: Evenement) -> Option<u64> {
+        match self.echeances[evenement as usize] {
+            JAMAIS => None,
+            echeance => Some(echeance),
+        }
+    }
+
+    pub fn get_prochaine_echeance(&self) -> Option<u64> {
+        match self.prochaine {
+            JAMAIS => None,
+            echeance => Some(echeance),
+        }
+    }
+
+    pub fn empreinte(&self, empreinte: &mut Empreinte) {
+        empreinte.ecrire_u64(self.maintenant);
+        for echeance in self.echeances {
+            empreinte.ecrire_u64(echeance);
+        }
+    }
+
+    fn calculer_prochaine(&mut self) {
+        self.prochaine = self.echeances.iter().copied().min().unwrap_or(JAMAIS);
+    }
+
+    // Retire et renvoie l'événement échu le plus ancien, avec son échéance. Les événements
+    // reprogrammés à partir de cette échéance ne dérivent pas quand elle est traitée en retard.
+    pub fn prochain_echu(&mut self) -> Option<(Evenement, u64)> {
+        if self.prochaine > self.maintenant {
+            return None;
+        }
+        let (index, echeance) = self
+            .echeances
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by_key(|&(_, echeance)| echeance)?;
+        self.echeances[index] = JAMAIS;
+        self.calculer_prochaine();
+        Some((EVENEMENTS[index], echeance))
+    }
+}
@@ -1,17 +1,28 @@
+// Attributs d'un sprite (octet 3 de l'OAM) ou d'un carreau de la carte CGB (VRAM banque 1).
+//  Bit 7 - Priorité : l'arrière-plan passe devant (couleurs 1 à 3)
+//  Bit 6 - Retournement vertical
+//  Bit 5 - Retournement horizontal
+//  Bit 4 - Palette DMG des sprites (OBP0 ou OBP1)
+//  Bit 3 - Banque de VRAM des données du carreau (CGB)
+//  Bit 2-0 - Palette CGB
 pub struct Attribut {
     pub priority : bool,
     pub flip_x : bool,
     pub flip_y : bool,
     pub palette_number : usize,
+    pub banque : u8,
+    pub palette_cgb : u8,
 }
 
 impl From<u8> for Attribut {
     fn from(byte: u8) -> Attribut {
         Attribut {
-            priority: byte & (1 << 4) != 0,
-            flip_y: byte & (1 << 3) != 0,
-            flip_x: byte & (1 << 2) != 0,
-            palette_number: byte as usize & (1 << 1)
+            priority: byte & (1 << 7) != 0,
+            flip_y: byte & (1 << 6) != 0,
+            flip_x: byte & (1 << 5) != 0,
+            palette_number: usize::from(byte >> 4 & 0x01),
+            banque: byte >> 3 & 0x01,
+            palette_cgb: byte & 0x07,
         }
     }
-}
\ No newline at end of file
+}
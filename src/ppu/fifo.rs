@@ -0,0 +1,374 @@
+// Rendu au dot près du mode 3 : un fetcher lit les carreaux de l'arrière-plan ou de la fenêtre
+// dans la VRAM et remplit une FIFO de pixels, vidée vers l'écran à raison d'un pixel par dot.
+// Les sprites de la ligne suspendent l'affichage le temps de lire leurs carreaux, qui sont
+// mélangés dans une seconde FIFO. La durée du mode 3 dépend donc du défilement fin (SCX % 8),
+// de la fenêtre et des sprites, et les écritures dans les registres en cours de ligne prennent
+// effet au pixel près.
+
+use crate::ppu::attribut::Attribut;
+use crate::ppu::{Ppu, Sprite, SCREEN_WIDTH};
+
+// Le premier carreau de la ligne est lu deux fois : la première lecture est perdue.
+const DOTS_PREMIERE_LECTURE: u8 = 6;
+
+// Lecture des deux octets d'une ligne d'un carreau de sprite, pendant laquelle l'affichage est suspendu.
+const DOTS_LECTURE_SPRITE: u8 = 6;
+
+#[derive(Debug, Copy, Clone, Default)]
+struct PixelFond {
+    couleur: u8,
+    // Priorité de l'arrière-plan sur les sprites (attribut CGB).
+    priorite: bool,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct PixelSprite {
+    couleur: u8,
+    // Entrée de l'OAM, pour la priorité entre sprites sur CGB.
+    index: usize,
+    palette: usize,
+    // Le sprite passe derrière les couleurs 1 à 3 de l'arrière-plan.
+    derriere: bool,
+}
+
+// File de 8 pixels au plus.
+#[derive(Debug, Copy, Clone, Default)]
+struct File<T: Copy + Default> {
+    pixels: [T; 8],
+    debut: usize,
+    longueur: usize,
+}
+
+impl<T: Copy + Default> File<T> {
+    fn is_empty(&self) -> bool {
+        self.longueur == 0
+    }
+
+    fn push(&mut self, pixel: T) {
+        self.pixels[(self.debut + self.longueur) % 8] = pixel;
+        self.longueur += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.longueur == 0 {
+            return None;
+        }
+        let pixel = self.pixels[self.debut];
+        self.debut = (self.debut + 1) % 8;
+        self.longueur -= 1;
+        Some(pixel)
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut T {
+        &mut self.pixels[(self.debut + i) % 8]
+    }
+
+    fn vider(&mut self) {
+        self.longueur = 0;
+    }
+}
+
+// Chaque étape de lecture dure 2 dots. L'envoi est retenté à chaque dot jusqu'à ce que la FIFO
+// de l'arrière-plan soit vide.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum EtapeFetcher {
+    Numero,
+    DonneeBasse,
+    DonneeHaute,
+    Envoi,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Fetcher {
+    etape: EtapeFetcher,
+    dots: u8,
+    // Colonne du prochain carreau, comptée depuis le début de la ligne ou de la fenêtre.
+    x: u8,
+    fenetre: bool,
+    adresse: u16,
+    numero: u8,
+    attribut: u8,
+    basse: u8,
+    haute: u8,
+}
+
+impl Fetcher {
+    fn new(fenetre: bool) -> Fetcher {
+        Fetcher {
+            etape: EtapeFetcher::Numero,
+            dots: 0,
+            x: 0,
+            fenetre,
+            adresse: 0x9800,
+            numero: 0x00,
+            attribut: 0x00,
+            basse: 0x00,
+            haute: 0x00,
+        }
+    }
+}
+
+// État du mode 3 de la ligne en cours.
+#[derive(Debug, Copy, Clone)]
+pub struct Transfert {
+    fond: File<PixelFond>,
+    sprites: File<PixelSprite>,
+    fetcher: Fetcher,
+    // Pixels déjà envoyés à l'écran.
+    x: u8,
+    // Pixels de l'arrière-plan écartés en début de ligne pour le défilement fin.
+    a_ecarter: u8,
+    attente: u8,
+    // Sprites retenus pour la ligne, dans l'ordre de l'OAM, et ceux déjà lus.
+    ligne: [Sprite; 40],
+    nombre: usize,
+    lus: u64,
+    // Sprite en cours de lecture et dots restants.
+    lecture: Option<(usize, u8)>,
+    pub dots: u32,
+}
+
+impl Transfert {
+    pub fn is_termine(&self) -> bool {
+        usize::from(self.x) >= SCREEN_WIDTH
+    }
+
+    // Minorant des dots restants : chaque pixel, affiché ou écarté, prend au moins un dot.
+    pub fn get_dots_restants(&self) -> u32 {
+        let pixels = SCREEN_WIDTH as u32 - u32::from(self.x);
+        (pixels + u32::from(self.a_ecarter) + u32::from(self.attente)).max(1)
+    }
+
+    pub fn get_x(&self) -> u8 {
+        self.x
+    }
+
+    // La fenêtre a été dessinée sur la ligne.
+    pub fn is_fenetre(&self) -> bool {
+        self.fetcher.fenetre
+    }
+}
+
+impl Ppu {
+    // Début du mode 3 avec les sprites retenus par l'OAM scan.
+    pub(super) fn commencer_transfert(&mut self) -> Transfert {
+        let (ligne, nombre) = self.selectionner_sprites();
+        Transfert {
+            fond: File::default(),
+            sprites: File::default(),
+            fetcher: Fetcher::new(false),
+            x: 0,
+            a_ecarter: self.scroll_x % 8,
+            attente: DOTS_PREMIERE_LECTURE,
+            ligne,
+            nombre,
+            lus: 0,
+            lecture: None,
+            dots: 0,
+        }
+    }
+
+    // Fait avancer le mode 3 de `dots` dots, sans dépasser la fin de la ligne.
+    pub fn avancer_transfert(&mut self, dots: u64) {
+        let Some(mut transfert) = self.transfert else {
+            return;
+        };
+        for _ in 0..dots {
+            if transfert.is_termine() {
+                break;
+            }
+            self.executer_dot(&mut transfert);
+        }
+        self.transfert = Some(transfert);
+    }
+
+    fn executer_dot(&mut self, transfert: &mut Transfert) {
+        transfert.dots += 1;
+        if transfert.attente > 0 {
+            transfert.attente -= 1;
+            return;
+        }
+
+        // L'affichage reste suspendu pendant la lecture d'un sprite.
+        if let Some((index, restants)) = transfert.lecture {
+            if restants > 1 {
+                transfert.lecture = Some((index, restants - 1));
+            } else {
+                transfert.lecture = None;
+                self.melanger_sprite(transfert, index);
+            }
+            return;
+        }
+
+        if self.lcd_control.has_bit(1) {
+            let suivant = (0..transfert.nombre).find(|&i| {
+                transfert.lus & (1 << i) == 0
+                    && u16::from(transfert.ligne[i].x) <= u16::from(transfert.x) + 8
+            });
+            if let Some(index) = suivant {
+                // La lecture du sprite attend que l'arrière-plan ait des pixels à afficher.
+                if transfert.fond.is_empty() {
+                    self.avancer_fetcher(transfert);
+                    return;
+                }
+                transfert.lus |= 1 << index;
+                transfert.lecture = Some((index, DOTS_LECTURE_SPRITE - 1));
+                return;
+            }
+        }
+
+        if !transfert.fetcher.fenetre
+            && self.is_fenetre_affichee()
+            && u16::from(transfert.x) + 7 >= u16::from(self.window_x)
+        {
+            // La FIFO est vidée et le fetcher repart du premier carreau de la fenêtre. Avec WX
+            // de 0 à 6, les premières colonnes de la fenêtre sont écartées.
+            transfert.fond.vider();
+            transfert.fetcher = Fetcher::new(true);
+            if transfert.x == 0 {
+                transfert.a_ecarter = 7u8.saturating_sub(self.window_x);
+            }
+        }
+
+        self.avancer_fetcher(transfert);
+        let Some(fond) = transfert.fond.pop() else {
+            return;
+        };
+        if transfert.a_ecarter > 0 {
+            transfert.a_ecarter -= 1;
+            return;
+        }
+        let sprite = transfert.sprites.pop();
+        self.afficher_pixel(transfert.x as usize, fond, sprite);
+        transfert.x += 1;
+    }
+
+    fn avancer_fetcher(&mut self, transfert: &mut Transfert) {
+        let fetcher = &mut transfert.fetcher;
+        if fetcher.etape == EtapeFetcher::Envoi {
+            if transfert.fond.is_empty() {
+                self.envoyer_carreau(transfert);
+            }
+            return;
+        }
+        fetcher.dots += 1;
+        if fetcher.dots < 2 {
+            return;
+        }
+        fetcher.dots = 0;
+        match fetcher.etape {
+            EtapeFetcher::Numero => {
+                let carte = self.get_carte(fetcher.fenetre);
+                let (x, y) = if fetcher.fenetre {
+                    (fetcher.x, self.ligne_fenetre)
+                } else {
+                    let x = (self.scroll_x >> 3).wrapping_add(fetcher.x);
+                    (x, self.scroll_y.wrapping_add(self.lcdc_y))
+                };
+                fetcher.adresse = carte + (u16::from(y) >> 3 & 31) * 32 + (u16::from(x) & 31);
+                fetcher.numero = self.get_vram(0, fetcher.adresse);
+                fetcher.attribut = self.get_vram(1, fetcher.adresse);
+                fetcher.etape = EtapeFetcher::DonneeBasse;
+            }
+            EtapeFetcher::DonneeBasse => {
+                let adresse = self.get_adresse_ligne_fond(fetcher);
+                fetcher.basse = self.get_vram(0, adresse);
+                fetcher.etape = EtapeFetcher::DonneeHaute;
+            }
+            EtapeFetcher::DonneeHaute => {
+                let adresse = self.get_adresse_ligne_fond(fetcher);
+                fetcher.haute = self.get_vram(0, adresse + 1);
+                fetcher.etape = EtapeFetcher::Envoi;
+                // Le carreau est envoyé dès le dot suivant si la FIFO est vide.
+            }
+            EtapeFetcher::Envoi => {}
+        }
+    }
+
+    // Adresse de la ligne du carreau à afficher, selon l'adressage de LCDC bit 4.
+    fn get_adresse_ligne_fond(&self, fetcher: &Fetcher) -> u16 {
+        let y = if fetcher.fenetre {
+            self.ligne_fenetre
+        } else {
+            self.scroll_y.wrapping_add(self.lcdc_y)
+        } % 8;
+        let y = if Attribut::from(fetcher.attribut).flip_y { 7 - y } else { y };
+        self.get_adresse_carreau_fond(fetcher.numero) + u16::from(y) * 2
+    }
+
+    fn envoyer_carreau(&mut self, transfert: &mut Transfert) {
+        let fetcher = &mut transfert.fetcher;
+        let attribut = Attribut::from(fetcher.attribut);
+        for i in 0..8 {
+            let bit = if attribut.flip_x { i } else { 7 - i };
+            let couleur = (fetcher.haute >> bit & 0x01) << 1 | (fetcher.basse >> bit & 0x01);
+            transfert.fond.push(PixelFond {
+                couleur,
+                priorite: attribut.priority,
+            });
+        }
+        fetcher.x = fetcher.x.wrapping_add(1);
+        fetcher.etape = EtapeFetcher::Numero;
+    }
+
+    // Les pixels du sprite remplacent les pixels transparents de la FIFO des sprites. Sur DMG, un
+    // sprite lu plus tôt (plus à gauche, ou avant dans l'OAM) reste devant ; sur CGB, le premier
+    // dans l'OAM passe devant.
+    fn melanger_sprite(&mut self, transfert: &mut Transfert, index: usize) {
+        let sprite = transfert.ligne[index];
+        let attribut = Attribut::from(sprite.attribut);
+        let hauteur = self.get_hauteur_sprites();
+        let numero = if hauteur == 16 {
+            sprite.numero & 0xFE
+        } else {
+            sprite.numero
+        };
+        let ligne = self.lcdc_y.wrapping_add(16).wrapping_sub(sprite.y);
+        let ligne = if attribut.flip_y { hauteur - 1 - ligne } else { ligne };
+        let adresse = 0x8000 + u16::from(numero) * 16 + u16::from(ligne) * 2;
+        let basse = self.get_vram(0, adresse);
+        let haute = self.get_vram(0, adresse + 1);
+
+        // Partie du sprite déjà à gauche du pixel courant (sprites coupés par le bord gauche).
+        let decalage = (u16::from(transfert.x) + 8 - u16::from(sprite.x)) as usize;
+        while transfert.sprites.longueur < 8 {
+            transfert.sprites.push(PixelSprite::default());
+        }
+        for i in decalage..8 {
+            let bit = if attribut.flip_x { i } else { 7 - i };
+            let couleur = (haute >> bit & 0x01) << 1 | (basse >> bit & 0x01);
+            let pixel = transfert.sprites.get_mut(i - decalage);
+            let devant = self.cgb && couleur != 0 && sprite.index < pixel.index;
+            if pixel.couleur == 0 || devant {
+                *pixel = PixelSprite {
+                    couleur,
+                    index: sprite.index,
+                    palette: attribut.palette_number,
+                    derriere: attribut.priority,
+                };
+            }
+        }
+    }
+
+    fn afficher_pixel(&mut self, x: usize, fond: PixelFond, sprite: Option<PixelSprite>) {
+        // Arrière-plan désactivé (LCDC bit 0) : il est blanc et passe derrière tous les sprites.
+        let fond = if self.lcd_control.has_bit(0) {
+            fond
+        } else {
+            PixelFond::default()
+        };
+        let sprite = sprite.filter(|sprite| {
+            sprite.couleur != 0
+                && self.lcd_control.has_bit(1)
+                && !((fond.priorite || sprite.derriere) && fond.couleur != 0)
+        });
+        let nuance = match sprite {
+            Some(sprite) if sprite.palette == 1 => {
+                self.get_nuance_de_gris(self.object_pallete_1, usize::from(sprite.couleur))
+            }
+            Some(sprite) => self.get_nuance_de_gris(self.object_pallete_0, usize::from(sprite.couleur)),
+            None => self.get_nuance_de_gris(self.bg_palette, usize::from(fond.couleur)),
+        };
+        self.set_nuances_de_gris(x, nuance);
+    }
+}
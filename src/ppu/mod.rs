@@ -1,14 +1,26 @@
 mod attribut;
+mod fifo;
 mod lcd;
+pub mod visualiseur;
+#[cfg(test)]
+mod tests;
 
 use crate::ppu::attribut::Attribut;
+use crate::ppu::fifo::Transfert;
 use crate::ppu::lcd::{LcdControl, LcdStatus};
+use crate::empreinte::Empreinte;
 use crate::memoire::Memoire;
 use crate::mmu::InterruptFlag;
 
 pub const SCREEN_WIDTH: usize = 160;
 pub const SCREEN_HEIGHT: usize = 144;
 
+// Nombre de sprites affichés au plus sur une ligne.
+const SPRITES_PAR_LIGNE: usize = 10;
+
+// Durée de l'OAM scan (mode 2), du dot 0 au dot 79.
+pub const DOTS_OAM: u32 = 80;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Pixel {
     pub r: u8,
@@ -30,12 +42,44 @@ impl Pixel {
     }
 }
 
+// Entrée de l'OAM retenue pour une ligne.
+#[derive(Debug, Copy, Clone, Default)]
+struct Sprite {
+    index: usize,
+    y: u8,
+    x: u8,
+    numero: u8,
+    attribut: u8,
+}
+
+// Rendu de l'image : ligne entière au début du HBlank (rapide), ou pixel FIFO au dot près, qui
+// reproduit les effets en cours de ligne et la durée variable du mode 3.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Rendu {
+    Ligne,
+    Fifo,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Etape {
+    Allumage,
+    FinOam,
+    FinTransfert,
+    FinLigne,
+    // LY passe à 0 peu après le début de la ligne 153.
+    Ligne153,
+    FinTrame,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Ppu {
     pub data: [Pixel; SCREEN_WIDTH * SCREEN_HEIGHT],
     pub interrupt: u8,
+    // Une trame complète vient d'être affichée. Jamais levé quand l'écran est éteint.
     pub vblank: bool,
     pub hblank: bool,
+    // La première trame après l'allumage de l'écran n'est pas affichée.
+    pub trame_masquee: bool,
     lcd_control: LcdControl,
     lcd_status: LcdStatus,
 
@@ -55,6 +99,10 @@ pub struct Ppu {
     // Spécifie les positions supérieure et gauche de la zone de la fenêtre.
     window_y: u8,
     window_x: u8,
+    // La fenêtre n'apparaît qu'après une ligne où LY = WY, jusqu'à la fin de la trame.
+    fenetre_declenchee: bool,
+    // Ligne de la fenêtre à afficher, qui n'avance que sur les lignes où la fenêtre a été dessinée.
+    ligne_fenetre: u8,
 
     // Ce registre attribue des nuances de gris aux numéros de couleur des carreaux BG et Window.
     //   0  Blanc
@@ -72,6 +120,14 @@ pub struct Ppu {
    
     // Ce registre à 1 bit sélectionne la banque de mémoire vidéo (VRAM) actuelle.
     vram_bank: usize,
+    // Mémoire des palettes CGB : 8 palettes de 4 couleurs RGB555 (octet de poids faible en
+    // premier) pour le fond et pour les sprites, lues et écrites par BCPS/BCPD (FF68/FF69) et
+    // OCPS/OCPD (FF6A/FF6B).
+    palettes_fond: [u8; 64],
+    palettes_sprites: [u8; 64],
+    // Bits 5-0 : index dans la mémoire des palettes, bit 7 : incrément après chaque écriture.
+    index_palettes_fond: u8,
+    index_palettes_sprites: u8,
 
     // Table d'attributs des sprites VRAM (OAM)
     //  Octet 0 - Position Y
@@ -83,7 +139,27 @@ pub struct Ppu {
     //  Octet 3 - Attributs/Flags
     oam: [u8; 0xA0],
     priorities: [(bool, usize); SCREEN_WIDTH],
-    dots: u32,
+    // Ligne d'interruption STAT : OU des sources activées. L'interruption n'est demandée que
+    // lorsqu'elle passe à 1, une source ne la redemande pas tant qu'une autre la maintient.
+    ligne_stat: bool,
+    // Bug du DMG : une écriture dans STAT active toutes les sources pendant un cycle.
+    pub bug_stat: bool,
+    // Bug du DMG : un INC/DEC 16 bits dans FE00-FEFF pendant le mode 2 corrompt l'OAM.
+    pub corruption_oam: bool,
+    // Prochain changement de mode, programmé par la MMU.
+    etape: Etape,
+    pub rendu: Rendu,
+    // Priorité des sprites selon l'ordre de l'OAM (CGB) ou leur position horizontale (DMG).
+    pub cgb: bool,
+    // Limite de 10 sprites par ligne. Sans elle, les jeux qui font clignoter leurs sprites pour
+    // la contourner les affichent tous.
+    pub limite_sprites: bool,
+    // Entrées de l'OAM au-delà de la dixième sur au moins une ligne, un bit par entrée : trame
+    // en cours et dernière trame complète. Elles sont relevées même si la limite est désactivée.
+    sprites_ecartes_trame: u64,
+    sprites_ecartes: u64,
+    // Mode 3 en cours avec le rendu FIFO.
+    transfert: Option<Transfert>,
 }
 
 impl Ppu {
@@ -93,6 +169,7 @@ impl Ppu {
             interrupt: InterruptFlag::None as u8,
             vblank: false,
             hblank: false,
+            trame_masquee: false,
             lcd_control: LcdControl::new(),
             lcd_status: LcdStatus::new(),
             scroll_x: 0x00,
@@ -101,78 +178,258 @@ impl Ppu {
             ly_compare: 0x00,
             window_y: 0x00,
             window_x: 0x00,
+            fenetre_declenchee: false,
+            ligne_fenetre: 0x00,
             bg_palette: 0x00,
             object_pallete_0: 0x00,
             object_pallete_1: 0x01,
             vram: [0x00; 0x4000],
             vram_bank: 0x00,
+            // Couleurs blanches (7FFF), comme après la boot ROM CGB.
+            palettes_fond: std::array::from_fn(|i| if i % 2 == 0 { 0xFF } else { 0x7F }),
+            palettes_sprites: std::array::from_fn(|i| if i % 2 == 0 { 0xFF } else { 0x7F }),
+            index_palettes_fond: 0x00,
+            index_palettes_sprites: 0x00,
             oam: [0x00; 0xA0],
             priorities: [(true, 0); SCREEN_WIDTH],
-            dots: 0,
+            ligne_stat: false,
+            bug_stat: false,
+            corruption_oam: false,
+            etape: Etape::Allumage,
+            rendu: Rendu::Ligne,
+            cgb: false,
+            limite_sprites: true,
+            sprites_ecartes_trame: 0,
+            sprites_ecartes: 0,
+            transfert: None,
         }
     }
 
-    pub fn run_cycles(&mut self, cycles: u32) {
-        if !self.lcd_control.has_bit(7) {
-            return;
-        }
-        self.hblank = false;
-        if cycles == 0 {
-            return;
+    pub fn is_allume(&self) -> bool {
+        self.lcd_control.has_bit(7)
+    }
+
+    // Image visible sur l'écran : blanche lorsque l'écran est éteint ou que la première trame
+    // après l'allumage est en cours, la dernière trame rendue sinon.
+    pub fn get_ecran(&self) -> [Pixel; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        if self.is_allume() && !self.trame_masquee {
+            self.data
+        } else {
+            [Pixel::new(); SCREEN_WIDTH * SCREEN_HEIGHT]
         }
-        let c = (cycles - 1) / 80 + 1;
-        for i in 0..c {
-            if i == (c - 1) {
-                self.dots += cycles % 80
-            } else {
-                self.dots += 80
-            }
-            let d = self.dots;
-            self.dots %= 456;
-            if d != self.dots {
-                self.lcdc_y = (self.lcdc_y + 1) % 154;
-                if self.lcd_status.lyc_interrupt_enabled && self.lcdc_y == self.ly_compare {
-                    self.interrupt |= InterruptFlag::LCDStat as u8;
-                }
+    }
+
+    // Traite le changement de mode programmé et renvoie le nombre de dots jusqu'au suivant.
+    // Une ligne dure 456 dots : OAM scan (mode 2) du dot 0 au dot 79, transfert (mode 3) à partir
+    // du dot 80, au moins 172 dots, puis HBlank (mode 0). Les lignes 144 à 153 sont en VBlank (mode 1).
+    pub fn evenement(&mut self) -> u32 {
+        match self.etape {
+            Etape::Allumage => {
+                // L'écran vient d'être allumé, la ligne 0 a commencé un dot plus tôt.
+                self.debut_ligne() - 1
             }
-            if self.lcdc_y >= 144 {
-                if self.lcd_status.mode == 1 {
-                    continue;
-                }
-                self.lcd_status.mode = 1;
-                self.vblank = true;
-                self.interrupt |= InterruptFlag::VBlank as u8;
-                if self.lcd_status.m1_vblank_interrupt_enabled {
-                    self.interrupt |= InterruptFlag::LCDStat as u8;
-                }
-            } else if self.dots <= 80 {
-                if self.lcd_status.mode == 2 {
-                    continue;
-                }
-                self.lcd_status.mode = 2;
-                if self.lcd_status.m2_oam_interrupt_enabled {
-                    self.interrupt |= InterruptFlag::LCDStat as u8;
-                }
-            } else if self.dots <= (80 + 172) {
+            Etape::FinOam => {
                 self.lcd_status.mode = 3;
-            } else {
-                if self.lcd_status.mode == 0 {
-                    continue;
+                self.actualiser_stat();
+                self.etape = Etape::FinTransfert;
+                if self.rendu == Rendu::Fifo {
+                    let transfert = self.commencer_transfert();
+                    self.transfert = Some(transfert);
+                    return transfert.get_dots_restants();
                 }
+                172
+            }
+            Etape::FinTransfert => {
+                // Avec le rendu FIFO, la MMU a fait avancer le transfert jusqu'à l'échéance : le
+                // mode 0 commence lorsque le dernier pixel a été envoyé.
+                let duree_transfert = match self.transfert {
+                    Some(transfert) if !transfert.is_termine() => return transfert.get_dots_restants(),
+                    Some(transfert) => transfert.dots,
+                    None => 172,
+                };
                 self.lcd_status.mode = 0;
                 self.hblank = true;
-                if self.lcd_status.m0_hblank_interrupt_enabled {
-                    self.interrupt |= InterruptFlag::LCDStat as u8;
-                }
+                self.actualiser_stat();
                 // Rendu de la ligne de balayage
-                if self.lcd_control.has_bit(0) {
-                    self.dessiner_arriere_plan();
+                let fenetre = match self.transfert.take() {
+                    Some(transfert) => transfert.is_fenetre(),
+                    None => {
+                        if self.lcd_control.has_bit(0) {
+                            self.dessiner_arriere_plan();
+                        }
+                        if self.lcd_control.has_bit(1) {
+                            self.dessiner_sprites();
+                        }
+                        self.is_fenetre_affichee()
+                    }
+                };
+                if fenetre {
+                    self.ligne_fenetre = self.ligne_fenetre.wrapping_add(1);
                 }
-                if self.lcd_control.has_bit(1) {
-                    self.dessiner_sprites();
+                self.etape = Etape::FinLigne;
+                456 - DOTS_OAM - duree_transfert
+            }
+            Etape::FinLigne => {
+                self.hblank = false;
+                self.lcdc_y += 1;
+                self.debut_ligne()
+            }
+            Etape::Ligne153 => {
+                self.lcdc_y = 0;
+                self.actualiser_stat();
+                self.etape = Etape::FinTrame;
+                456 - 4
+            }
+            Etape::FinTrame => self.debut_ligne(),
+        }
+    }
+
+    // Sources de l'interruption STAT actives. `toutes` simule le bug du DMG à l'écriture dans STAT.
+    fn get_ligne_stat(&self, toutes: bool) -> bool {
+        let status = &self.lcd_status;
+        let coincidence = self.lcdc_y == self.ly_compare;
+        (coincidence && (status.lyc_interrupt_enabled || toutes))
+            || (status.mode == 0 && (status.m0_hblank_interrupt_enabled || toutes))
+            || (status.mode == 1 && (status.m1_vblank_interrupt_enabled || toutes))
+            || (status.mode == 2 && status.m2_oam_interrupt_enabled)
+    }
+
+    // Réévalue la ligne STAT après un changement de mode, de LY, de LYC ou de STAT.
+    fn actualiser_stat(&mut self) {
+        let ligne = self.is_allume() && self.get_ligne_stat(false);
+        if ligne && !self.ligne_stat {
+            self.interrupt |= InterruptFlag::LCDStat as u8;
+        }
+        self.ligne_stat = ligne;
+    }
+
+    fn debut_ligne(&mut self) -> u32 {
+        if self.lcdc_y >= 144 {
+            if self.lcd_status.mode != 1 {
+                self.lcd_status.mode = 1;
+                self.vblank = !self.trame_masquee;
+                if self.trame_masquee {
+                    // La trame masquée n'est jamais affichée, même après son VBlank.
+                    self.data = [Pixel::new(); SCREEN_WIDTH * SCREEN_HEIGHT];
+                    self.trame_masquee = false;
                 }
+                self.interrupt |= InterruptFlag::VBlank as u8;
+                self.sprites_ecartes = std::mem::take(&mut self.sprites_ecartes_trame);
             }
+            self.actualiser_stat();
+            if self.lcdc_y == 153 {
+                self.etape = Etape::Ligne153;
+                return 4;
+            }
+            self.etape = Etape::FinLigne;
+            return 456;
+        }
+        if self.lcdc_y == 0 {
+            self.fenetre_declenchee = false;
+            self.ligne_fenetre = 0;
         }
+        // WY est comparé à LY au début de chaque ligne, que la fenêtre soit activée ou non.
+        if self.lcdc_y == self.window_y {
+            self.fenetre_declenchee = true;
+        }
+        self.lcd_status.mode = 2;
+        self.actualiser_stat();
+        self.etape = Etape::FinOam;
+        DOTS_OAM
+    }
+
+    // L'index reboucle sur les 64 octets de la mémoire des palettes.
+    fn incrementer_index_palettes(index: u8) -> u8 {
+        if index & 0x80 == 0 {
+            return index;
+        }
+        0x80 | (index + 1) & 0x3F
+    }
+
+    // Registres et mémoires du PPU. L'image affichée a sa propre empreinte.
+    pub fn empreinte(&self, empreinte: &mut Empreinte) {
+        let status = &self.lcd_status;
+        empreinte.ecrire(&[
+            self.lcd_control.data,
+            u8::from(status.lyc_interrupt_enabled),
+            u8::from(status.m2_oam_interrupt_enabled),
+            u8::from(status.m1_vblank_interrupt_enabled),
+            u8::from(status.m0_hblank_interrupt_enabled),
+            status.mode,
+            self.scroll_y,
+            self.scroll_x,
+            self.lcdc_y,
+            self.ly_compare,
+            self.window_y,
+            self.window_x,
+            u8::from(self.fenetre_declenchee),
+            self.ligne_fenetre,
+            self.bg_palette,
+            self.object_pallete_0,
+            self.object_pallete_1,
+            self.vram_bank as u8,
+            self.etape as u8,
+            u8::from(self.ligne_stat),
+            u8::from(self.trame_masquee),
+            self.interrupt,
+        ]);
+        empreinte.ecrire(&self.vram);
+        empreinte.ecrire(&self.oam);
+        // La mémoire des palettes n'existe pas sur DMG : elle n'entre pas dans l'empreinte.
+        if self.cgb {
+            empreinte.ecrire(&[self.index_palettes_fond, self.index_palettes_sprites]);
+            empreinte.ecrire(&self.palettes_fond);
+            empreinte.ecrire(&self.palettes_sprites);
+        }
+        if let Some(transfert) = &self.transfert {
+            empreinte.ecrire_u8(transfert.get_x());
+            empreinte.ecrire_u64(u64::from(transfert.dots));
+        }
+    }
+
+    // Fenêtre activée et déclenchée par WY sur cette trame. Avec WX au-delà de 166, elle est
+    // hors de l'écran. Sur DMG, LCDC bit 0 masque aussi la fenêtre : son compteur de lignes
+    // n'avance pas.
+    fn is_fenetre_affichee(&self) -> bool {
+        self.lcd_control.has_bit(5)
+            && (self.cgb || self.lcd_control.has_bit(0))
+            && self.fenetre_declenchee
+            && self.window_x <= 166
+    }
+
+    // Mode 3 en cours avec le rendu FIFO : le transfert doit suivre le temps au dot près.
+    pub fn is_transfert_en_cours(&self) -> bool {
+        self.transfert.is_some()
+    }
+
+    pub fn get_mode(&self) -> u8 {
+        self.lcd_status.mode
+    }
+
+    // Le CPU n'accède pas à la VRAM pendant le transfert, ni à l'OAM pendant le scan et le
+    // transfert : les lectures renvoient 0xFF et les écritures sont ignorées.
+    pub fn is_accessible(&self, addr: u16) -> bool {
+        match addr {
+            0x8000..=0x9FFF => self.lcd_status.mode != 3,
+            0xFE00..=0xFE9F => self.lcd_status.mode < 2,
+            _ => true,
+        }
+    }
+
+    // L'OAM est lue par rangées de 8 octets, une tous les 4 dots du mode 2. Lorsque le bug se
+    // déclenche, le premier mot de la rangée lue est mélangé avec la rangée précédente et les
+    // trois autres mots sont recopiés depuis celle-ci. La première rangée n'est pas affectée.
+    pub fn corrompre_oam(&mut self, rangee: usize) {
+        if rangee == 0 || rangee >= 0xA0 / 8 {
+            return;
+        }
+        let debut = rangee * 8;
+        let precedente = debut - 8;
+        let mot = |i: usize| u16::from_le_bytes([self.oam[i], self.oam[i + 1]]);
+        let (a, b, c) = (mot(debut), mot(precedente), mot(precedente + 4));
+        let valeur = ((a ^ c) & (b ^ c)) ^ c;
+        self.oam[debut..debut + 2].copy_from_slice(&valeur.to_le_bytes());
+        self.oam.copy_within(precedente + 2..precedente + 8, debut + 2);
     }
 
     fn get_vram(&self, num: u8, addr: u16) -> u8 {
@@ -201,48 +458,47 @@ impl Ppu {
         self.data[(self.lcdc_y as usize * SCREEN_WIDTH) + index] = Pixel::from_greyscale(g);
     }
 
-    fn dessiner_arriere_plan(&mut self) {
-        let show_window = self.lcd_control.has_bit(5) && self.window_y <= self.lcdc_y;
-        let tile_base = if self.lcd_control.has_bit(4) {
-            0x8000
+    // Carte des carreaux de la fenêtre (LCDC.6) ou du fond (LCDC.3).
+    pub fn get_carte(&self, fenetre: bool) -> u16 {
+        let bit = if fenetre { 6 } else { 3 };
+        if self.lcd_control.has_bit(bit) {
+            0x9C00
         } else {
-            0x8800
-        };
-        let window_x = self.window_x.wrapping_sub(7);
-        let picture_y = if show_window {
-            self.lcdc_y.wrapping_sub(self.window_y)
+            0x9800
+        }
+    }
+
+    // Données d'un carreau du fond ou de la fenêtre selon LCDC.4 : numéros non signés à partir
+    // de 8000, ou signés à partir de 9000.
+    fn get_adresse_carreau_fond(&self, numero: u8) -> u16 {
+        if self.lcd_control.has_bit(4) {
+            0x8000 + u16::from(numero) * 16
         } else {
-            self.scroll_y.wrapping_add(self.lcdc_y)
-        };
-        let tile_y = (u16::from(picture_y) >> 3) & 31;
+            (0x9000 + i32::from(numero as i8) * 16) as u16
+        }
+    }
+
+    fn dessiner_arriere_plan(&mut self) {
+        let show_window = self.is_fenetre_affichee();
+        // La fenêtre commence à la colonne WX - 7 : avec WX de 0 à 6, ses premières colonnes sont
+        // hors de l'écran.
+        let window_x = i16::from(self.window_x) - 7;
 
         for x in 0..SCREEN_WIDTH {
-            let picture_x = if show_window && x as u8 >= window_x {
-                x as u8 - window_x
+            let in_window = show_window && x as i16 >= window_x;
+            let (picture_x, picture_y) = if in_window {
+                ((x as i16 - window_x) as u8, self.ligne_fenetre)
             } else {
-                self.scroll_x.wrapping_add(x as u8)
+                (
+                    self.scroll_x.wrapping_add(x as u8),
+                    self.scroll_y.wrapping_add(self.lcdc_y),
+                )
             };
             let tile_x = (u16::from(picture_x) >> 3) & 31;
-            let background_base_addr = if show_window && x as u8 >= window_x {
-                if self.lcd_control.has_bit(6) {
-                    0x9C00
-                } else {
-                    0x9800
-                }
-            } else if self.lcd_control.has_bit(3) {
-                0x9C00
-            } else {
-                0x9800
-            };
-            let tile_addr = background_base_addr + tile_y * 32 + tile_x;
+            let tile_y = (u16::from(picture_y) >> 3) & 31;
+            let tile_addr = self.get_carte(in_window) + tile_y * 32 + tile_x;
             let tile_number = self.get_vram(0, tile_addr);
-            let tile_offset = if self.lcd_control.has_bit(4) {
-                i16::from(tile_number)
-            } else {
-                i16::from(tile_number as i8) + 128
-            } as u16
-                * 16;
-            let tile_location = tile_base + tile_offset;
+            let tile_location = self.get_adresse_carreau_fond(tile_number);
             let tile_attribut = Attribut::from(self.get_vram(1, tile_addr));
             let tile_y = if tile_attribut.flip_y {
                 7 - picture_y % 8
@@ -273,29 +529,69 @@ impl Ppu {
         }
     }
 
-    fn dessiner_sprites(&mut self) {
+    // OAM scan : sprites qui recouvrent la ligne, dans l'ordre de l'OAM. Seuls les 10 premiers
+    // sont affichés, quelle que soit leur position horizontale, sauf si la limite est désactivée.
+    fn selectionner_sprites(&mut self) -> ([Sprite; 40], usize) {
+        let hauteur = self.get_hauteur_sprites();
+        let mut sprites = [Sprite::default(); 40];
+        let mut nombre = 0;
+        let mut ecartes = 0;
+        for (index, entree) in self.oam.chunks(4).enumerate() {
+            if self.lcdc_y.wrapping_add(16).wrapping_sub(entree[0]) < hauteur {
+                if nombre >= SPRITES_PAR_LIGNE {
+                    ecartes |= 1 << index;
+                    if self.limite_sprites {
+                        continue;
+                    }
+                }
+                sprites[nombre] = Sprite {
+                    index,
+                    y: entree[0],
+                    x: entree[1],
+                    numero: entree[2],
+                    attribut: entree[3],
+                };
+                nombre += 1;
+            }
+        }
+        if self.lcd_control.has_bit(1) {
+            self.sprites_ecartes_trame |= ecartes;
+        }
+        (sprites, nombre)
+    }
+
+    fn get_hauteur_sprites(&self) -> u8 {
         // Taille des tuiles de sprites 8x8 ou 8x16 (2 empilées verticalement).
-        let sprite_size = if self.lcd_control.has_bit(2) { 16 } else { 8 };
-        for i in 0..40 {
-            let sprite_addr = 0xFE00 + (i as u16) * 4;
-            let picture_y = self.get_octet(sprite_addr).wrapping_sub(16);
-            let picture_x = self.get_octet(sprite_addr + 1).wrapping_sub(8);
-            let tile_number = self.get_octet(sprite_addr + 2)
+        if self.lcd_control.has_bit(2) {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn dessiner_sprites(&mut self) {
+        let sprite_size = self.get_hauteur_sprites();
+        let (mut sprites, nombre) = self.selectionner_sprites();
+        let sprites = &mut sprites[..nombre];
+        // Sur DMG, le sprite le plus à gauche passe devant, puis le premier dans l'OAM.
+        // Sur CGB, seul l'ordre de l'OAM compte.
+        if !self.cgb {
+            sprites.sort_by_key(|sprite| sprite.x);
+        }
+        // Un pixel appartient au premier sprite opaque, même si l'arrière-plan le recouvre ensuite.
+        let mut occupes = [false; SCREEN_WIDTH];
+        for sprite in sprites.iter() {
+            let picture_y = sprite.y.wrapping_sub(16);
+            let picture_x = sprite.x.wrapping_sub(8);
+            let tile_number = sprite.numero
                 & if self.lcd_control.has_bit(2) {
                     0xFE
                 } else {
                     0xFF
                 };
-            let tile_attribut = Attribut::from(self.get_octet(sprite_addr + 3));
+            let tile_attribut = Attribut::from(sprite.attribut);
 
-            // Si c'est le cas, la ligne de balayage est en dehors de la zone qui nous intéresse.
-            if picture_y <= 0xFF - sprite_size + 1 {
-                if self.lcdc_y < picture_y || self.lcdc_y > picture_y + sprite_size - 1 {
-                    continue;
-                }
-            } else if self.lcdc_y > picture_y.wrapping_add(sprite_size) - 1 {
-                continue;
-            }
+            // Si c'est le cas, le sprite est entièrement hors de l'écran.
             if picture_x >= (SCREEN_WIDTH as u8) && picture_x <= (0xFF - 7) {
                 continue;
             }
@@ -314,7 +610,8 @@ impl Ppu {
                 };
 
             for x in 0..8 {
-                if picture_x.wrapping_add(x) >= (SCREEN_WIDTH as u8) {
+                let pixel_x = picture_x.wrapping_add(x) as usize;
+                if pixel_x >= SCREEN_WIDTH {
                     continue;
                 }
                 let tile_x = if tile_attribut.flip_x { 7 - x } else { x };
@@ -325,12 +622,13 @@ impl Ppu {
                     0
                 };
                 let color = color_high | color_low;
-                if color == 0 {
+                if color == 0 || occupes[pixel_x] {
                     continue;
                 }
+                occupes[pixel_x] = true;
 
                 // Confirme la priorité de l'arrière-plan et du sprite.
-                let priority = self.priorities[picture_x.wrapping_add(x) as usize];
+                let priority = self.priorities[pixel_x];
                 let skip = if priority.0 {
                     priority.1 != 0
                 } else {
@@ -345,7 +643,7 @@ impl Ppu {
                 } else {
                     self.get_nuance_de_gris(self.object_pallete_0, color)
                 };
-                self.set_nuances_de_gris(picture_x.wrapping_add(x) as usize, color);
+                self.set_nuances_de_gris(pixel_x, color);
             }
         }
     }
@@ -395,7 +693,14 @@ impl Memoire for Ppu {
             0xFF4A => self.window_y,
             0xFF4B => self.window_x,
             0xFF4F => 0xFE | self.vram_bank as u8,
-            _ => panic!("ppu: invalid address {:#06X?}", addr),
+            // Les registres des palettes CGB n'existent pas sur DMG.
+            0xFF68..=0xFF6B if !self.cgb => 0xFF,
+            0xFF68 => self.index_palettes_fond | 0x40,
+            0xFF69 => self.palettes_fond[usize::from(self.index_palettes_fond & 0x3F)],
+            0xFF6A => self.index_palettes_sprites | 0x40,
+            0xFF6B => self.palettes_sprites[usize::from(self.index_palettes_sprites & 0x3F)],
+            // Registre inexistant (FF4C, FF4E), signalé par le mode strict : le bus flotte.
+            _ => 0xFF,
         }
     }
 
@@ -404,33 +709,60 @@ impl Memoire for Ppu {
             0x8000..=0x9FFF => self.vram[self.vram_bank * 0x2000 + addr as usize - 0x8000] = value,
             0xFE00..=0xFE9F => self.oam[addr as usize - 0xFE00] = value,
             0xFF40 => {
+                let allume = self.is_allume();
                 self.lcd_control.data = value;
-                if !self.lcd_control.has_bit(7) {
-                    self.dots = 0;
-                    self.lcdc_y = 0;
-                    self.lcd_status.mode = 0;
-                    // Clean l'écran
-                    self.data = [Pixel::new(); SCREEN_WIDTH * SCREEN_HEIGHT];
-                    self.vblank = true;
+                match (allume, self.is_allume()) {
+                    (false, true) => self.trame_masquee = true,
+                    // Écran éteint : LY reste à 0 et STAT indique le mode 0 jusqu'au rallumage.
+                    // Aucune trame n'est signalée, l'écran est blanc (voir get_ecran).
+                    (true, false) => {
+                        self.etape = Etape::Allumage;
+                        self.transfert = None;
+                        self.hblank = false;
+                        self.lcdc_y = 0;
+                        self.lcd_status.mode = 0;
+                        self.ligne_stat = false;
+                    }
+                    _ => {}
                 }
             }
             0xFF41 => {
+                // Sur DMG, l'écriture active brièvement toutes les sources : en HBlank, en VBlank
+                // ou sur coïncidence LY=LYC, une interruption est demandée si la ligne était basse.
+                if self.bug_stat && !self.cgb && self.is_allume() && !self.ligne_stat && self.get_ligne_stat(true) {
+                    self.interrupt |= InterruptFlag::LCDStat as u8;
+                }
                 self.lcd_status.lyc_interrupt_enabled = value & 0x40 != 0x00;
                 self.lcd_status.m2_oam_interrupt_enabled = value & 0x20 != 0x00;
                 self.lcd_status.m1_vblank_interrupt_enabled = value & 0x10 != 0x00;
                 self.lcd_status.m0_hblank_interrupt_enabled = value & 0x08 != 0x00;
+                self.actualiser_stat();
             }
             0xFF42 => self.scroll_y = value,
             0xFF43 => self.scroll_x = value,
             0xFF44 => {}
-            0xFF45 => self.ly_compare = value,
+            0xFF45 => {
+                self.ly_compare = value;
+                self.actualiser_stat();
+            }
             0xFF47 => self.bg_palette = value, 
             0xFF48 => self.object_pallete_0 = value,
             0xFF49 => self.object_pallete_1 = value,
             0xFF4A => self.window_y = value,
             0xFF4B => self.window_x = value,
             0xFF4F => self.vram_bank = (value & 0x01) as usize,
-            _ => panic!("ppu: invalid address {:#06X?}", addr),
+            0xFF68..=0xFF6B if !self.cgb => {}
+            0xFF68 => self.index_palettes_fond = value & 0xBF,
+            0xFF69 => {
+                self.palettes_fond[usize::from(self.index_palettes_fond & 0x3F)] = value;
+                self.index_palettes_fond = Self::incrementer_index_palettes(self.index_palettes_fond);
+            }
+            0xFF6A => self.index_palettes_sprites = value & 0xBF,
+            0xFF6B => {
+                self.palettes_sprites[usize::from(self.index_palettes_sprites & 0x3F)] = value;
+                self.index_palettes_sprites = Self::incrementer_index_palettes(self.index_palettes_sprites);
+            }
+            _ => {}
         }
     }
 }
@@ -0,0 +1,391 @@
+// Tests du PPU sur une VRAM et une OAM construites à la main. Les changements de mode sont
+// traités directement par `evenement`, sans MMU ni planificateur.
+
+use crate::memoire::Memoire;
+use crate::mmu::InterruptFlag;
+use crate::ppu::{Ppu, Rendu, SCREEN_WIDTH};
+
+// Écran, fond et sprites activés, carte du fond en 9800, fenêtre en 9C00, carreaux en 8000.
+const LCDC: u8 = 0xD3;
+
+const BLANC: u8 = 0xFF;
+const GRIS_CLAIR: u8 = 0xC0;
+const GRIS_FONCE: u8 = 0x60;
+const NOIR: u8 = 0x00;
+
+// Palettes identité : la couleur 0 est blanche, la couleur 3 noire.
+fn allumer(rendu: Rendu) -> Ppu {
+    let mut ppu = Ppu::new();
+    ppu.rendu = rendu;
+    ppu.set_octet(0xFF47, 0xE4);
+    ppu.set_octet(0xFF48, 0xE4);
+    ppu.set_octet(0xFF49, 0xE4);
+    ppu.set_octet(0xFF40, LCDC);
+    ppu
+}
+
+// Carreau uni de la couleur donnée.
+fn remplir_carreau(ppu: &mut Ppu, numero: u8, couleur: u8) {
+    let adresse = 0x8000 + u16::from(numero) * 16;
+    for ligne in 0..8 {
+        ppu.set_octet(adresse + ligne * 2, if couleur & 0x01 != 0 { 0xFF } else { 0x00 });
+        ppu.set_octet(adresse + ligne * 2 + 1, if couleur & 0x02 != 0 { 0xFF } else { 0x00 });
+    }
+}
+
+fn placer_sprite(ppu: &mut Ppu, index: u16, y: u8, x: u8, numero: u8) {
+    let adresse = 0xFE00 + index * 4;
+    ppu.set_octet(adresse, y);
+    ppu.set_octet(adresse + 1, x);
+    ppu.set_octet(adresse + 2, numero);
+    ppu.set_octet(adresse + 3, 0x00);
+}
+
+// Traite le prochain changement de mode, après avoir terminé le mode 3 en cours.
+fn evenement(ppu: &mut Ppu) -> u32 {
+    ppu.avancer_transfert(u64::from(u32::MAX));
+    ppu.evenement()
+}
+
+// Avance jusqu'au début du mode 3 de la ligne.
+fn aller_au_transfert(ppu: &mut Ppu, ligne: u8) {
+    while ppu.lcdc_y != ligne || ppu.get_mode() != 3 {
+        evenement(ppu);
+    }
+}
+
+// Durée du mode 3 de la ligne, en dots, l'image de la ligne étant rendue.
+fn duree_transfert(ppu: &mut Ppu, ligne: u8) -> u32 {
+    aller_au_transfert(ppu, ligne);
+    ppu.avancer_transfert(u64::from(u32::MAX));
+    let dots = ppu.transfert.map(|transfert| transfert.dots).unwrap_or(172);
+    ppu.evenement();
+    dots
+}
+
+fn get_pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+    ppu.data[y * SCREEN_WIDTH + x].r
+}
+
+// Le mode 3 dure 172 dots, plus les SCX % 8 pixels écartés en début de ligne.
+#[test]
+fn fifo_duree_selon_defilement_fin() {
+    for scx in 0..16 {
+        let mut ppu = allumer(Rendu::Fifo);
+        ppu.set_octet(0xFF43, scx);
+        assert_eq!(duree_transfert(&mut ppu, 0), 172 + u32::from(scx % 8), "SCX={}", scx);
+    }
+}
+
+// Le fetcher repart du premier carreau de la fenêtre, ce qui coûte 6 dots au milieu de la ligne.
+#[test]
+fn fifo_fenetre_relance_le_fetcher() {
+    let mut ppu = allumer(Rendu::Fifo);
+    remplir_carreau(&mut ppu, 1, 3);
+    for i in 0..32 * 32 {
+        ppu.set_octet(0x9C00 + i, 1);
+    }
+    ppu.set_octet(0xFF40, LCDC | 0x20);
+    ppu.set_octet(0xFF4B, 87);
+    assert_eq!(duree_transfert(&mut ppu, 0), 178);
+    assert_eq!(get_pixel(&ppu, 79, 0), BLANC);
+    assert_eq!(get_pixel(&ppu, 80, 0), NOIR);
+    assert_eq!(get_pixel(&ppu, 159, 0), NOIR);
+
+    // À WX = 7, la fenêtre commence avec la ligne, sans relance.
+    ppu.set_octet(0xFF4B, 7);
+    assert_eq!(duree_transfert(&mut ppu, 1), 172);
+    assert_eq!(get_pixel(&ppu, 0, 1), NOIR);
+}
+
+// Chaque sprite de la ligne suspend l'affichage le temps de lire son carreau. Un sprite hors de
+// l'écran à droite ne coûte rien.
+#[test]
+fn fifo_penalite_sprite() {
+    for x in [0, 1, 4, 8, 9, 12, 16, 80, 167] {
+        let mut ppu = allumer(Rendu::Fifo);
+        placer_sprite(&mut ppu, 0, 16, x, 0);
+        let penalite = duree_transfert(&mut ppu, 0) - 172;
+        assert!((6..=11).contains(&penalite), "X={} : {} dots", x, penalite);
+    }
+
+    let mut ppu = allumer(Rendu::Fifo);
+    placer_sprite(&mut ppu, 0, 16, 168, 0);
+    assert_eq!(duree_transfert(&mut ppu, 0), 172);
+
+    let mut ppu = allumer(Rendu::Fifo);
+    for index in 0..10 {
+        placer_sprite(&mut ppu, index, 16, 40, 0);
+    }
+    assert!(duree_transfert(&mut ppu, 0) >= 172 + 10 * 6);
+}
+
+// Une écriture dans BGP au milieu du mode 3 ne change que les pixels suivants.
+#[test]
+fn fifo_ecriture_en_cours_de_ligne() {
+    let mut ppu = allumer(Rendu::Fifo);
+    aller_au_transfert(&mut ppu, 0);
+    while ppu.transfert.unwrap().get_x() < 80 {
+        ppu.avancer_transfert(1);
+    }
+    ppu.set_octet(0xFF47, 0xE7);
+    evenement(&mut ppu);
+    assert_eq!(get_pixel(&ppu, 0, 0), BLANC);
+    assert_eq!(get_pixel(&ppu, 79, 0), BLANC);
+    assert_eq!(get_pixel(&ppu, 80, 0), NOIR);
+    assert_eq!(get_pixel(&ppu, 159, 0), NOIR);
+
+    // Le rendu par ligne applique l'écriture à toute la ligne.
+    let mut ppu = allumer(Rendu::Ligne);
+    aller_au_transfert(&mut ppu, 0);
+    ppu.set_octet(0xFF47, 0xE7);
+    evenement(&mut ppu);
+    assert_eq!(get_pixel(&ppu, 0, 0), NOIR);
+}
+
+// Seuls les 10 premiers sprites de la ligne dans l'OAM sont retenus, quelle que soit leur position.
+#[test]
+fn sprites_limite_de_dix_par_ligne() {
+    for rendu in [Rendu::Ligne, Rendu::Fifo] {
+        let mut ppu = allumer(rendu);
+        remplir_carreau(&mut ppu, 1, 3);
+        // Sprites dans l'ordre inverse de leur position : les deux derniers sont les plus à gauche.
+        for index in 0..12 {
+            placer_sprite(&mut ppu, index, 16, 8 + 132 - index as u8 * 12, 1);
+        }
+        let (sprites, nombre) = ppu.selectionner_sprites();
+        assert_eq!(nombre, 10);
+        assert!(sprites[..nombre].iter().enumerate().all(|(i, sprite)| sprite.index == i));
+        duree_transfert(&mut ppu, 0);
+        assert_eq!(get_pixel(&ppu, 132, 0), NOIR, "{:?}", rendu);
+        assert_eq!(get_pixel(&ppu, 24, 0), NOIR, "{:?}", rendu);
+        assert_eq!(get_pixel(&ppu, 12, 0), BLANC, "{:?}", rendu);
+        assert_eq!(get_pixel(&ppu, 0, 0), BLANC, "{:?}", rendu);
+
+        assert_eq!(ppu.sprites_ecartes_trame, 0b1100_0000_0000);
+
+        // Sans la limite, les entrées au-delà de la dixième sont affichées mais toujours relevées.
+        ppu.limite_sprites = false;
+        ppu.sprites_ecartes_trame = 0;
+        assert_eq!(ppu.selectionner_sprites().1, 12);
+        assert_eq!(ppu.sprites_ecartes_trame, 0b1100_0000_0000);
+        duree_transfert(&mut ppu, 1);
+        assert_eq!(get_pixel(&ppu, 12, 1), NOIR, "{:?}", rendu);
+        assert_eq!(get_pixel(&ppu, 0, 1), NOIR, "{:?}", rendu);
+    }
+}
+
+// Deux sprites qui se chevauchent sur les colonnes 12 à 15 : l'entrée 0 (gris clair) en X = 20 et
+// l'entrée 1 (gris foncé) en X = 16, ou à la même position.
+fn dessiner_chevauchement(rendu: Rendu, cgb: bool, x1: u8) -> u8 {
+    let mut ppu = allumer(rendu);
+    ppu.cgb = cgb;
+    remplir_carreau(&mut ppu, 1, 1);
+    remplir_carreau(&mut ppu, 2, 2);
+    placer_sprite(&mut ppu, 0, 16, 20, 1);
+    placer_sprite(&mut ppu, 1, 16, x1, 2);
+    duree_transfert(&mut ppu, 0);
+    get_pixel(&ppu, 13, 0)
+}
+
+// Sur DMG, le sprite le plus à gauche passe devant, puis le premier dans l'OAM.
+#[test]
+fn sprites_priorite_dmg() {
+    for rendu in [Rendu::Ligne, Rendu::Fifo] {
+        assert_eq!(dessiner_chevauchement(rendu, false, 16), GRIS_FONCE, "{:?}", rendu);
+        assert_eq!(dessiner_chevauchement(rendu, false, 20), GRIS_CLAIR, "{:?}", rendu);
+    }
+}
+
+// Sur CGB, seul l'ordre de l'OAM compte.
+#[test]
+fn sprites_priorite_cgb() {
+    for rendu in [Rendu::Ligne, Rendu::Fifo] {
+        assert_eq!(dessiner_chevauchement(rendu, true, 16), GRIS_CLAIR, "{:?}", rendu);
+        assert_eq!(dessiner_chevauchement(rendu, true, 20), GRIS_CLAIR, "{:?}", rendu);
+    }
+}
+
+// Carte de la fenêtre : colonne 0 noire, colonnes suivantes gris foncé. Le carreau 3 n'est noir
+// que sur sa ligne 2, pour vérifier la ligne de la fenêtre affichée.
+fn preparer_fenetre(rendu: Rendu, wx: u8) -> Ppu {
+    let mut ppu = allumer(rendu);
+    remplir_carreau(&mut ppu, 1, 3);
+    remplir_carreau(&mut ppu, 2, 2);
+    ppu.set_octet(0x8000 + 3 * 16 + 4, 0xFF);
+    ppu.set_octet(0x8000 + 3 * 16 + 5, 0xFF);
+    for i in 0..32 * 32 {
+        ppu.set_octet(0x9C00 + i, 2);
+    }
+    ppu.set_octet(0x9C00, 1);
+    ppu.set_octet(0xFF40, LCDC | 0x20);
+    ppu.set_octet(0xFF4B, wx);
+    ppu
+}
+
+// Avec WX de 0 à 6, les 7 - WX premières colonnes de la fenêtre sont hors de l'écran. À WX = 166,
+// seule la dernière colonne de l'écran est couverte.
+#[test]
+fn fenetre_wx_aux_bords() {
+    for rendu in [Rendu::Ligne, Rendu::Fifo] {
+        for wx in 0..7 {
+            let mut ppu = preparer_fenetre(rendu, wx);
+            duree_transfert(&mut ppu, 0);
+            // Le premier carreau de la fenêtre se termine à la colonne WX.
+            let x = usize::from(wx);
+            assert_eq!(get_pixel(&ppu, x, 0), NOIR, "{:?} WX={}", rendu, wx);
+            assert_eq!(get_pixel(&ppu, x + 1, 0), GRIS_FONCE, "{:?} WX={}", rendu, wx);
+        }
+
+        let mut ppu = preparer_fenetre(rendu, 166);
+        duree_transfert(&mut ppu, 0);
+        assert_eq!(get_pixel(&ppu, 158, 0), BLANC, "{:?}", rendu);
+        assert_eq!(get_pixel(&ppu, 159, 0), NOIR, "{:?}", rendu);
+        assert_eq!(ppu.ligne_fenetre, 1, "{:?}", rendu);
+
+        let mut ppu = preparer_fenetre(rendu, 167);
+        duree_transfert(&mut ppu, 0);
+        assert_eq!(get_pixel(&ppu, 159, 0), BLANC, "{:?}", rendu);
+        assert_eq!(ppu.ligne_fenetre, 0, "{:?}", rendu);
+    }
+}
+
+// Le compteur de lignes de la fenêtre n'avance pas sur les lignes où elle est masquée, par
+// LCDC bit 5 ou, sur DMG, par LCDC bit 0 : elle reprend là où elle s'était arrêtée.
+#[test]
+fn fenetre_masquee_en_cours_de_trame() {
+    for rendu in [Rendu::Ligne, Rendu::Fifo] {
+        for masque in [0x20, 0x01] {
+            let mut ppu = preparer_fenetre(rendu, 7);
+            for i in 0..32 * 32 {
+                ppu.set_octet(0x9C00 + i, 3);
+            }
+            duree_transfert(&mut ppu, 0);
+            duree_transfert(&mut ppu, 1);
+            ppu.set_octet(0xFF40, (LCDC | 0x20) & !masque);
+            duree_transfert(&mut ppu, 2);
+            duree_transfert(&mut ppu, 3);
+            assert_eq!(ppu.ligne_fenetre, 2, "{:?} masque {:#04X}", rendu, masque);
+            ppu.set_octet(0xFF40, LCDC | 0x20);
+            duree_transfert(&mut ppu, 4);
+            assert_eq!(get_pixel(&ppu, 0, 4), NOIR, "{:?} masque {:#04X}", rendu, masque);
+            assert_eq!(ppu.ligne_fenetre, 3, "{:?} masque {:#04X}", rendu, masque);
+        }
+    }
+}
+
+// Avance jusqu'au début du mode donné sur la ligne donnée.
+fn aller_au_mode(ppu: &mut Ppu, ligne: u8, mode: u8) {
+    while ppu.lcdc_y != ligne || ppu.get_mode() != mode {
+        evenement(ppu);
+    }
+}
+
+// Interruption STAT demandée depuis le dernier appel.
+fn prendre_stat(ppu: &mut Ppu) -> bool {
+    let stat = ppu.interrupt & InterruptFlag::LCDStat as u8 != 0;
+    ppu.interrupt = InterruptFlag::None as u8;
+    stat
+}
+
+// La ligne STAT est le OU des sources : tant qu'une source la maintient, une autre ne demande pas
+// de nouvelle interruption.
+#[test]
+fn stat_blocage() {
+    let mut ppu = allumer(Rendu::Ligne);
+    ppu.set_octet(0xFF45, 3);
+    ppu.set_octet(0xFF41, 0x48);
+    aller_au_mode(&mut ppu, 2, 0);
+    assert!(prendre_stat(&mut ppu));
+    // Le HBlank de la ligne 2 maintient la ligne jusqu'à la coïncidence LY = LYC de la ligne 3,
+    // qui la maintient jusqu'au HBlank suivant.
+    aller_au_mode(&mut ppu, 3, 2);
+    assert!(!prendre_stat(&mut ppu));
+    aller_au_mode(&mut ppu, 3, 0);
+    assert!(!prendre_stat(&mut ppu));
+    // Ligne 4 : aucune source pendant le mode 2, le HBlank redemande l'interruption.
+    aller_au_mode(&mut ppu, 4, 2);
+    assert!(!prendre_stat(&mut ppu));
+    aller_au_mode(&mut ppu, 4, 0);
+    assert!(prendre_stat(&mut ppu));
+}
+
+// Les écritures dans LYC et STAT réévaluent la ligne immédiatement.
+#[test]
+fn stat_reevaluee_aux_ecritures() {
+    let mut ppu = allumer(Rendu::Ligne);
+    ppu.set_octet(0xFF41, 0x40);
+    aller_au_mode(&mut ppu, 5, 3);
+    prendre_stat(&mut ppu);
+    ppu.set_octet(0xFF45, 5);
+    assert!(prendre_stat(&mut ppu));
+    // Déjà haute : une nouvelle coïncidence ne redemande rien.
+    ppu.set_octet(0xFF45, 5);
+    assert!(!prendre_stat(&mut ppu));
+
+    ppu.set_octet(0xFF45, 0);
+    ppu.set_octet(0xFF41, 0x00);
+    aller_au_mode(&mut ppu, 6, 0);
+    prendre_stat(&mut ppu);
+    ppu.set_octet(0xFF41, 0x08);
+    assert!(prendre_stat(&mut ppu));
+}
+
+// LY passe à 0 4 dots après le début de la ligne 153 : la coïncidence avec LYC = 0 y est vue
+// pendant le VBlank.
+#[test]
+fn stat_ligne_153() {
+    let mut ppu = allumer(Rendu::Ligne);
+    ppu.set_octet(0xFF45, 0);
+    ppu.set_octet(0xFF41, 0x40);
+    aller_au_mode(&mut ppu, 153, 1);
+    prendre_stat(&mut ppu);
+    assert_eq!(ppu.get_octet(0xFF44), 153);
+    assert_eq!(evenement(&mut ppu), 456 - 4);
+    assert_eq!(ppu.get_octet(0xFF44), 0);
+    assert_eq!(ppu.get_octet(0xFF41) & 0x07, 0x05);
+    assert!(prendre_stat(&mut ppu));
+    // La ligne reste haute au début de la ligne 0 suivante.
+    evenement(&mut ppu);
+    assert_eq!(ppu.get_mode(), 2);
+    assert!(!prendre_stat(&mut ppu));
+}
+
+// Sur DMG, une écriture dans STAT pendant le HBlank demande une interruption, même sans source
+// activée. L'option est désactivée par défaut et sans effet sur CGB.
+#[test]
+fn stat_bug_dmg() {
+    for (bug_stat, cgb, attendue) in [(true, false, true), (false, false, false), (true, true, false)] {
+        let mut ppu = allumer(Rendu::Ligne);
+        ppu.bug_stat = bug_stat;
+        ppu.cgb = cgb;
+        ppu.set_octet(0xFF45, 100);
+        aller_au_mode(&mut ppu, 2, 0);
+        prendre_stat(&mut ppu);
+        ppu.set_octet(0xFF41, 0x00);
+        assert_eq!(prendre_stat(&mut ppu), attendue, "bug {} cgb {}", bug_stat, cgb);
+    }
+}
+
+// Le premier mot de la rangée est mélangé avec les mots 0 et 2 de la rangée précédente, les trois
+// autres sont recopiés depuis celle-ci. La première rangée et les rangées hors de l'OAM sont
+// épargnées.
+#[test]
+fn corruption_oam() {
+    let mut ppu = Ppu::new();
+    for i in 0..0xA0 {
+        ppu.set_octet(0xFE00 + i, (i as u8).wrapping_mul(37));
+    }
+    let avant = ppu.oam;
+    ppu.corrompre_oam(0);
+    ppu.corrompre_oam(20);
+    assert_eq!(ppu.oam, avant);
+
+    ppu.corrompre_oam(3);
+    let mot = |i: usize| u16::from_le_bytes([avant[i], avant[i + 1]]);
+    let (a, b, c) = (mot(24), mot(16), mot(20));
+    assert_eq!(&ppu.oam[24..26], &(((a ^ c) & (b ^ c)) ^ c).to_le_bytes());
+    assert_eq!(&ppu.oam[26..32], &avant[18..24]);
+    assert_eq!(&ppu.oam[..24], &avant[..24]);
+    assert_eq!(&ppu.oam[32..], &avant[32..]);
+}
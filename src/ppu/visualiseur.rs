@@ -0,0 +1,429 @@
+// Vues de débogage de la mémoire vidéo, rendues en RGBA indépendamment de l'image affichée.
+use std::fmt;
+
+use crate::ppu::attribut::Attribut;
+use crate::ppu::{Pixel, Ppu, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Les 384 carreaux d'une banque de VRAM (8000-97FF) sont rangés par lignes de 16.
+const CARREAUX_PAR_LIGNE: usize = 16;
+const CARREAUX_PAR_BANQUE: usize = 384;
+const LARGEUR_BANQUE: usize = CARREAUX_PAR_LIGNE * 8;
+
+// Une carte couvre 32x32 carreaux, soit 256x256 pixels.
+const TAILLE_CARTE: usize = 256;
+
+// Cadres superposés à la carte : partie affichée du fond et de la fenêtre.
+const COULEUR_FOND: Pixel = Pixel { r: 0xFF, g: 0x00, b: 0x00 };
+const COULEUR_FENETRE: Pixel = Pixel { r: 0x00, g: 0x60, b: 0xFF };
+
+// Vue des sprites : 40 cases de 8 colonnes, chacune avec une marge de 2 pixels autour d'un sprite
+// de 8x16. Le fond des cases montre les pixels transparents, un cadre rouge les sprites écartés.
+const SPRITES_PAR_LIGNE_VUE: usize = 8;
+const LARGEUR_CASE: usize = 12;
+const HAUTEUR_CASE: usize = 20;
+const COULEUR_TRANSPARENT: Pixel = Pixel { r: 0xC8, g: 0xD8, b: 0xE8 };
+const COULEUR_ECARTE: Pixel = Pixel { r: 0xFF, g: 0x00, b: 0x00 };
+
+// Vue des palettes : une pastille de 8x8 par couleur dans une case de 10 pixels. À gauche BGP
+// puis les 8 palettes CGB du fond, à droite OBP0, OBP1 puis les 8 palettes CGB des sprites.
+const TAILLE_PASTILLE: usize = 10;
+const LARGEUR_COLONNE_PALETTES: usize = 5 * TAILLE_PASTILLE;
+const PREMIERE_LIGNE_CGB: usize = 2;
+// Gris foncé autour des pastilles, pour distinguer les couleurs blanches.
+const COULEUR_CONTOUR: Pixel = Pixel { r: 0x40, g: 0x40, b: 0x40 };
+
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub largeur: usize,
+    pub hauteur: usize,
+    // 4 octets par pixel : rouge, vert, bleu, alpha.
+    pub rgba: Vec<u8>,
+}
+
+impl Image {
+    fn new(largeur: usize, hauteur: usize) -> Image {
+        Image {
+            largeur,
+            hauteur,
+            rgba: vec![0xFF; largeur * hauteur * 4],
+        }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        let i = (y * self.largeur + x) * 4;
+        self.rgba[i..i + 4].copy_from_slice(&[pixel.r, pixel.g, pixel.b, 0xFF]);
+    }
+
+    // Contour d'un rectangle qui reboucle sur les bords de l'image, comme le défilement.
+    fn dessiner_cadre(&mut self, x: usize, y: usize, largeur: usize, hauteur: usize, pixel: Pixel) {
+        for i in 0..largeur {
+            self.set_pixel((x + i) % self.largeur, y % self.hauteur, pixel);
+            self.set_pixel((x + i) % self.largeur, (y + hauteur - 1) % self.hauteur, pixel);
+        }
+        for j in 0..hauteur {
+            self.set_pixel(x % self.largeur, (y + j) % self.hauteur, pixel);
+            self.set_pixel((x + largeur - 1) % self.largeur, (y + j) % self.hauteur, pixel);
+        }
+    }
+}
+
+// Entrée de l'OAM décodée.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EntreeOam {
+    pub index: usize,
+    // Position du coin bas droit : le sprite est affiché en (x - 8, y - 16).
+    pub y: u8,
+    pub x: u8,
+    pub numero: u8,
+    pub attribut: u8,
+    pub priorite: bool,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // OBP0 ou OBP1 sur DMG.
+    pub palette: usize,
+    pub palette_cgb: u8,
+    pub banque: u8,
+    // Au-delà de la dixième sur au moins une ligne de la dernière trame : écartée par la limite
+    // de 10 sprites, ou affichée malgré tout lorsqu'elle est désactivée.
+    pub ecartee: bool,
+}
+
+impl EntreeOam {
+    // Entrée sous le pixel (x, y) de l'image renvoyée par Ppu::get_image_sprites.
+    pub fn sous(x: usize, y: usize) -> Option<usize> {
+        let colonne = x / LARGEUR_CASE;
+        let index = y / HAUTEUR_CASE * SPRITES_PAR_LIGNE_VUE + colonne;
+        (colonne < SPRITES_PAR_LIGNE_VUE && index < 40).then_some(index)
+    }
+}
+
+impl fmt::Display for EntreeOam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02} y={:3} x={:3} carreau ${:02X} attributs ${:02X} obp{} cgb{} banque {}",
+            self.index, i16::from(self.y) - 16, i16::from(self.x) - 8, self.numero, self.attribut,
+            self.palette, self.palette_cgb, self.banque
+        )?;
+        if self.flip_x {
+            write!(f, " flip-x")?;
+        }
+        if self.flip_y {
+            write!(f, " flip-y")?;
+        }
+        if self.priorite {
+            write!(f, " derrière")?;
+        }
+        if self.ecartee {
+            write!(f, " écartée")?;
+        }
+        Ok(())
+    }
+}
+
+// Palette DMG (registre de nuances de gris) ou CGB (8 palettes RGB555 du fond ou des sprites).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SourcePalette {
+    Bgp,
+    Obp0,
+    Obp1,
+    Fond(usize),
+    Sprites(usize),
+}
+
+impl fmt::Display for SourcePalette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourcePalette::Bgp => write!(f, "BGP"),
+            SourcePalette::Obp0 => write!(f, "OBP0"),
+            SourcePalette::Obp1 => write!(f, "OBP1"),
+            SourcePalette::Fond(palette) => write!(f, "fond {}", palette),
+            SourcePalette::Sprites(palette) => write!(f, "sprites {}", palette),
+        }
+    }
+}
+
+// Couleur `index` (0 à 3) d'une palette.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CouleurPalette {
+    pub source: SourcePalette,
+    pub index: usize,
+}
+
+impl CouleurPalette {
+    // Couleur sous le pixel (x, y) de l'image renvoyée par Ppu::get_image_palettes.
+    pub fn sous(x: usize, y: usize) -> Option<CouleurPalette> {
+        let (colonne, ligne) = (x / TAILLE_PASTILLE, y / TAILLE_PASTILLE);
+        let sprites = colonne >= 5;
+        let index = colonne % 5;
+        let source = match (sprites, ligne) {
+            (_, 10..) => return None,
+            (false, 0) => SourcePalette::Bgp,
+            (false, 1) => return None,
+            (true, 0) => SourcePalette::Obp0,
+            (true, 1) => SourcePalette::Obp1,
+            (false, ligne) => SourcePalette::Fond(ligne - PREMIERE_LIGNE_CGB),
+            (true, ligne) => SourcePalette::Sprites(ligne - PREMIERE_LIGNE_CGB),
+        };
+        (index < 4 && colonne < 10).then_some(CouleurPalette { source, index })
+    }
+}
+
+// Palette appliquée aux numéros de couleur des carreaux.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PaletteVue {
+    // Numéros de couleur bruts, du blanc (0) au noir (3).
+    Gris,
+    Bgp,
+    Obp0,
+    Obp1,
+}
+
+impl PaletteVue {
+    pub fn suivante(self) -> PaletteVue {
+        match self {
+            PaletteVue::Gris => PaletteVue::Bgp,
+            PaletteVue::Bgp => PaletteVue::Obp0,
+            PaletteVue::Obp0 => PaletteVue::Obp1,
+            PaletteVue::Obp1 => PaletteVue::Gris,
+        }
+    }
+}
+
+// Carreau de la vue des carreaux.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Carreau {
+    pub banque: usize,
+    // Numéro de 0 à 383 : les carreaux 0 à 255 sont ceux du mode 8000 de LCDC.4.
+    pub index: usize,
+    pub adresse: u16,
+}
+
+impl Carreau {
+    // Carreau sous le pixel (x, y) de l'image renvoyée par Ppu::get_image_carreaux.
+    pub fn sous(x: usize, y: usize) -> Option<Carreau> {
+        let banque = x / LARGEUR_BANQUE;
+        let index = y / 8 * CARREAUX_PAR_LIGNE + x % LARGEUR_BANQUE / 8;
+        if banque > 1 || index >= CARREAUX_PAR_BANQUE {
+            return None;
+        }
+        Some(Carreau {
+            banque,
+            index,
+            adresse: 0x8000 + index as u16 * 16,
+        })
+    }
+}
+
+impl Ppu {
+    fn get_valeur_palette(&self, palette: PaletteVue) -> u8 {
+        match palette {
+            PaletteVue::Gris => 0b1110_0100,
+            PaletteVue::Bgp => self.bg_palette,
+            PaletteVue::Obp0 => self.object_pallete_0,
+            PaletteVue::Obp1 => self.object_pallete_1,
+        }
+    }
+
+    // Carreau dont les données commencent à `adresse` (8000-97FF) dans la banque de VRAM donnée
+    // par les attributs, dessiné avec son coin haut gauche en (x, y). Avec `transparent`, la
+    // couleur 0 n'est pas dessinée, comme pour les sprites.
+    fn dessiner_carreau(
+        &self,
+        image: &mut Image,
+        adresse: u16,
+        attribut: &Attribut,
+        (x, y): (usize, usize),
+        palette: u8,
+        transparent: bool,
+    ) {
+        let debut = usize::from(attribut.banque) * 0x2000 + usize::from(adresse - 0x8000);
+        for ligne in 0..8 {
+            let ligne_donnees = if attribut.flip_y { 7 - ligne } else { ligne };
+            let bas = self.vram[debut + ligne_donnees * 2];
+            let haut = self.vram[debut + ligne_donnees * 2 + 1];
+            for colonne in 0..8 {
+                let bit = if attribut.flip_x { colonne } else { 7 - colonne };
+                let couleur = usize::from((haut >> bit & 0x01) << 1 | (bas >> bit & 0x01));
+                if transparent && couleur == 0 {
+                    continue;
+                }
+                let g = self.get_nuance_de_gris(palette, couleur);
+                image.set_pixel(x + colonne, y + ligne, Pixel::from_greyscale(g));
+            }
+        }
+    }
+
+    // Les deux banques côte à côte, 16 carreaux par ligne : 256x192 pixels.
+    pub fn get_image_carreaux(&self, palette: PaletteVue) -> Image {
+        let lignes = CARREAUX_PAR_BANQUE / CARREAUX_PAR_LIGNE;
+        let mut image = Image::new(2 * LARGEUR_BANQUE, lignes * 8);
+        let palette = self.get_valeur_palette(palette);
+        for banque in 0..2 {
+            for index in 0..CARREAUX_PAR_BANQUE {
+                let x = banque * LARGEUR_BANQUE + index % CARREAUX_PAR_LIGNE * 8;
+                let y = index / CARREAUX_PAR_LIGNE * 8;
+                let attribut = Attribut::from((banque as u8) << 3);
+                let adresse = 0x8000 + index as u16 * 16;
+                self.dessiner_carreau(&mut image, adresse, &attribut, (x, y), palette, false);
+            }
+        }
+        image
+    }
+
+    // Carte de 256x256 pixels à 9800 ou 9C00, avec l'adressage des carreaux de LCDC.4, la
+    // palette BGP et, sur CGB, les attributs de la banque 1. Les cadres montrent la partie
+    // affichée : SCX/SCY pour la carte du fond, WX/WY pour celle de la fenêtre.
+    pub fn get_image_carte(&self, carte: u16, cadres: bool) -> Image {
+        let mut image = Image::new(TAILLE_CARTE, TAILLE_CARTE);
+        for ligne in 0..32 {
+            for colonne in 0..32 {
+                let adresse = carte + ligne * 32 + colonne;
+                let numero = self.get_vram(0, adresse);
+                let attribut = Attribut::from(if self.cgb { self.get_vram(1, adresse) } else { 0x00 });
+                let donnees = self.get_adresse_carreau_fond(numero);
+                let (x, y) = (usize::from(colonne) * 8, usize::from(ligne) * 8);
+                self.dessiner_carreau(&mut image, donnees, &attribut, (x, y), self.bg_palette, false);
+            }
+        }
+        if !cadres {
+            return image;
+        }
+        if self.lcd_control.has_bit(0) && carte == self.get_carte(false) {
+            let (x, y) = (usize::from(self.scroll_x), usize::from(self.scroll_y));
+            image.dessiner_cadre(x, y, SCREEN_WIDTH, SCREEN_HEIGHT, COULEUR_FOND);
+        }
+        let fenetre_visible = self.lcd_control.has_bit(5)
+            && usize::from(self.window_y) < SCREEN_HEIGHT
+            && self.window_x <= 166;
+        if fenetre_visible && carte == self.get_carte(true) {
+            // Avec WX < 7, les premières colonnes de la fenêtre sont hors de l'écran.
+            let debut = 7_usize.saturating_sub(usize::from(self.window_x));
+            let fin = SCREEN_WIDTH + 7 - usize::from(self.window_x);
+            let hauteur = SCREEN_HEIGHT - usize::from(self.window_y);
+            image.dessiner_cadre(debut, 0, fin - debut, hauteur, COULEUR_FENETRE);
+        }
+        image
+    }
+
+    pub fn get_entrees_oam(&self) -> Vec<EntreeOam> {
+        self.oam
+            .chunks(4)
+            .enumerate()
+            .map(|(index, entree)| {
+                let attribut = Attribut::from(entree[3]);
+                EntreeOam {
+                    index,
+                    y: entree[0],
+                    x: entree[1],
+                    numero: entree[2],
+                    attribut: entree[3],
+                    priorite: attribut.priority,
+                    flip_x: attribut.flip_x,
+                    flip_y: attribut.flip_y,
+                    palette: attribut.palette_number,
+                    palette_cgb: attribut.palette_cgb,
+                    banque: attribut.banque,
+                    ecartee: self.sprites_ecartes & 1 << index != 0,
+                }
+            })
+            .collect()
+    }
+
+    // Les 40 sprites de l'OAM en 8x8 ou 8x16 selon LCDC.2, sans tenir compte de leur position,
+    // avec leur palette DMG et la banque de VRAM de leurs attributs.
+    pub fn get_image_sprites(&self) -> Image {
+        let lignes = 40 / SPRITES_PAR_LIGNE_VUE;
+        let mut image = Image::new(SPRITES_PAR_LIGNE_VUE * LARGEUR_CASE, lignes * HAUTEUR_CASE);
+        let hauteur = usize::from(self.get_hauteur_sprites());
+        for entree in self.get_entrees_oam() {
+            let x = entree.index % SPRITES_PAR_LIGNE_VUE * LARGEUR_CASE + 2;
+            let y = entree.index / SPRITES_PAR_LIGNE_VUE * HAUTEUR_CASE + 2;
+            if entree.ecartee {
+                image.dessiner_cadre(x - 2, y - 2, LARGEUR_CASE, HAUTEUR_CASE, COULEUR_ECARTE);
+            }
+            for j in 0..hauteur {
+                for i in 0..8 {
+                    image.set_pixel(x + i, y + j, COULEUR_TRANSPARENT);
+                }
+            }
+            // La banque de VRAM des attributs n'existe que sur CGB.
+            let attribut = Attribut::from(if self.cgb { entree.attribut } else { entree.attribut & !0x08 });
+            let palette = if entree.palette == 0 {
+                self.object_pallete_0
+            } else {
+                self.object_pallete_1
+            };
+            // En 8x16, le bit 0 du numéro est ignoré et le retournement vertical échange aussi
+            // les deux carreaux.
+            let numeros = if hauteur == 16 {
+                let haut = entree.numero & 0xFE;
+                if entree.flip_y { [haut | 0x01, haut] } else { [haut, haut | 0x01] }
+            } else {
+                [entree.numero, entree.numero]
+            };
+            for (i, numero) in numeros.iter().take(hauteur / 8).enumerate() {
+                let adresse = 0x8000 + u16::from(*numero) * 16;
+                self.dessiner_carreau(&mut image, adresse, &attribut, (x, y + i * 8), palette, true);
+            }
+        }
+        image
+    }
+
+    // Valeur brute d'une couleur : nuance de 0 à 3 pour les palettes DMG, RGB555 pour CGB.
+    pub fn get_valeur_couleur(&self, couleur: CouleurPalette) -> u16 {
+        let nuance = |registre: u8| u16::from(registre >> (2 * couleur.index) & 0x03);
+        let rgb = |memoire: &[u8; 64], palette: usize| {
+            let i = palette * 8 + couleur.index * 2;
+            u16::from_le_bytes([memoire[i], memoire[i + 1]])
+        };
+        match couleur.source {
+            SourcePalette::Bgp => nuance(self.bg_palette),
+            SourcePalette::Obp0 => nuance(self.object_pallete_0),
+            SourcePalette::Obp1 => nuance(self.object_pallete_1),
+            SourcePalette::Fond(palette) => rgb(&self.palettes_fond, palette),
+            SourcePalette::Sprites(palette) => rgb(&self.palettes_sprites, palette),
+        }
+    }
+
+    fn get_pixel_couleur(&self, couleur: CouleurPalette) -> Pixel {
+        let valeur = self.get_valeur_couleur(couleur);
+        match couleur.source {
+            SourcePalette::Bgp | SourcePalette::Obp0 | SourcePalette::Obp1 => {
+                Pixel::from_greyscale(self.get_nuance_de_gris(0b1110_0100, usize::from(valeur)))
+            }
+            // Composantes de 5 bits étendues à 8 bits.
+            _ => {
+                let composante = |decalage: u16| {
+                    let c = (valeur >> decalage & 0x1F) as u8;
+                    c << 3 | c >> 2
+                };
+                Pixel {
+                    r: composante(0),
+                    g: composante(5),
+                    b: composante(10),
+                }
+            }
+        }
+    }
+
+    // Toutes les palettes, DMG et CGB, en pastilles de couleur : 100x100 pixels.
+    pub fn get_image_palettes(&self) -> Image {
+        let mut image = Image::new(2 * LARGEUR_COLONNE_PALETTES, 10 * TAILLE_PASTILLE);
+        for y in (0..image.hauteur).step_by(TAILLE_PASTILLE) {
+            for x in (0..image.largeur).step_by(TAILLE_PASTILLE) {
+                let Some(couleur) = CouleurPalette::sous(x, y) else {
+                    continue;
+                };
+                let pixel = self.get_pixel_couleur(couleur);
+                for j in 0..TAILLE_PASTILLE {
+                    for i in 0..TAILLE_PASTILLE {
+                        let bord = i == 0 || j == 0 || i == TAILLE_PASTILLE - 1 || j == TAILLE_PASTILLE - 1;
+                        image.set_pixel(x + i, y + j, if bord { COULEUR_CONTOUR } else { pixel });
+                    }
+                }
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,69 @@
+// Temps passé dans les périphériques, mesuré avec la feature `profilage`. Le reste du temps
+// d'émulation revient au CPU : décodage, exécution et avance du temps à chaque cycle machine.
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use crate::planificateur::Evenement;
+
+// Chronométrer chaque accès mémoire coûterait plus cher que l'accès lui-même : un accès sur
+// ECHANTILLONNAGE est mesuré et sa durée compte pour tous.
+const ECHANTILLONNAGE: u32 = 32;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Profil {
+    // Changements de mode et rendu des lignes.
+    pub ppu: Duration,
+    // Accès mémoire (estimation), timer et port série.
+    pub mmu: Duration,
+    pub evenements: u64,
+    pub acces: u64,
+}
+
+pub struct Chronometre {
+    profil: Cell<Profil>,
+    // Durée d'une mesure à vide, retranchée de chaque mesure d'accès.
+    surcout: Duration,
+}
+
+impl Chronometre {
+    pub fn new() -> Chronometre {
+        let mesures = 10_000;
+        let debut = Instant::now();
+        for _ in 0..mesures {
+            std::hint::black_box(Instant::now().elapsed());
+        }
+        Chronometre {
+            profil: Cell::new(Profil::default()),
+            surcout: debut.elapsed() / mesures,
+        }
+    }
+
+    pub fn get_profil(&self) -> Profil {
+        self.profil.get()
+    }
+
+    pub fn ajouter_evenement(&self, evenement: Evenement, duree: Duration) {
+        let mut profil = self.profil.get();
+        match evenement {
+            Evenement::Ppu => profil.ppu += duree,
+            Evenement::Timer | Evenement::Serie | Evenement::Dma => profil.mmu += duree,
+        }
+        profil.evenements += 1;
+        self.profil.set(profil);
+    }
+
+    pub fn debut_acces(&self) -> Option<Instant> {
+        let mut profil = self.profil.get();
+        profil.acces += 1;
+        self.profil.set(profil);
+        profil.acces.is_multiple_of(u64::from(ECHANTILLONNAGE)).then(Instant::now)
+    }
+
+    pub fn fin_acces(&self, debut: Option<Instant>) {
+        if let Some(debut) = debut {
+            let mut profil = self.profil.get();
+            profil.mmu += debut.elapsed().saturating_sub(self.surcout) * ECHANTILLONNAGE;
+            self.profil.set(profil);
+        }
+    }
+}
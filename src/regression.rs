@@ -0,0 +1,138 @@
+// Tests de non-régression : une ROM est exécutée sans limitation de vitesse pendant un nombre de
+// trames fixé, avec les appuis de touches d'un script, et les empreintes de chaque trame sont
+// comparées à celles d'un fichier de référence.
+//
+// Script, une action par ligne ("#" pour les commentaires) :
+//   <trame> appui|relache <bouton>
+// avec bouton parmi droite, gauche, haut, bas, a, b, select, start. L'action est appliquée au début
+// de la trame indiquée, la première trame étant la trame 0.
+//
+// Fichier de référence, une ligne par trame :
+//   <trame> <empreinte de l'écran> <empreinte de l'état>
+// les empreintes étant en hexadécimal sur 16 chiffres.
+
+use crate::{Gameboy, GameboyButton};
+
+// Durée d'une trame : une trame est aussi comptée lorsque l'écran éteint ne produit pas de VBlank.
+const CYCLES_PAR_TRAME: u32 = 70224;
+
+#[derive(Clone, Copy)]
+pub struct Action {
+    pub trame: u32,
+    pub bouton: GameboyButton,
+    pub appui: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Empreintes {
+    pub ecran: u64,
+    pub etat: u64,
+}
+
+fn parse_bouton(nom: &str) -> Option<GameboyButton> {
+    match nom {
+        "droite" => Some(GameboyButton::Right),
+        "gauche" => Some(GameboyButton::Left),
+        "haut" => Some(GameboyButton::Up),
+        "bas" => Some(GameboyButton::Down),
+        "a" => Some(GameboyButton::A),
+        "b" => Some(GameboyButton::B),
+        "select" => Some(GameboyButton::Select),
+        "start" => Some(GameboyButton::Start),
+        _ => None,
+    }
+}
+
+// Lignes non vides et hors commentaires, avec leur numéro pour les messages d'erreur.
+fn lignes(contenu: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    contenu
+        .lines()
+        .enumerate()
+        .map(|(i, ligne)| (i + 1, ligne.split('#').next().unwrap_or("")))
+        .map(|(numero, ligne)| (numero, ligne.split_whitespace().collect::<Vec<&str>>()))
+        .filter(|(_, mots)| !mots.is_empty())
+}
+
+pub fn parse_script(contenu: &str) -> Result<Vec<Action>, String> {
+    let mut actions = Vec::new();
+    for (numero, mots) in lignes(contenu) {
+        let action = match mots.as_slice() {
+            [trame, action, bouton] => {
+                let trame = trame.parse().ok();
+                let appui = match *action {
+                    "appui" => Some(true),
+                    "relache" => Some(false),
+                    _ => None,
+                };
+                trame.zip(appui).zip(parse_bouton(bouton))
+            }
+            _ => None,
+        };
+        match action {
+            Some(((trame, appui), bouton)) => actions.push(Action { trame, bouton, appui }),
+            None => return Err(format!("script: ligne {} invalide", numero)),
+        }
+    }
+    actions.sort_by_key(|action| action.trame);
+    Ok(actions)
+}
+
+pub fn parse_reference(contenu: &str) -> Result<Vec<Empreintes>, String> {
+    let parse = |s: &str| u64::from_str_radix(s, 16).ok();
+    let mut empreintes = Vec::new();
+    for (numero, mots) in lignes(contenu) {
+        match mots.as_slice() {
+            [trame, ecran, etat] if trame.parse() == Ok(empreintes.len()) => {
+                match (parse(ecran), parse(etat)) {
+                    (Some(ecran), Some(etat)) => empreintes.push(Empreintes { ecran, etat }),
+                    _ => return Err(format!("référence: ligne {} invalide", numero)),
+                }
+            }
+            _ => return Err(format!("référence: ligne {} invalide", numero)),
+        }
+    }
+    Ok(empreintes)
+}
+
+pub fn formater_reference(empreintes: &[Empreintes]) -> String {
+    empreintes
+        .iter()
+        .enumerate()
+        .map(|(trame, empreintes)| {
+            format!("{} {:016x} {:016x}\n", trame, empreintes.ecran, empreintes.etat)
+        })
+        .collect()
+}
+
+// Exécute `trames` trames et renvoie les empreintes relevées à la fin de chacune.
+pub fn executer(gameboy: &mut Gameboy, script: &[Action], trames: u32) -> Vec<Empreintes> {
+    gameboy.set_vitesse_limitee(false);
+    let mut actions = script.iter().peekable();
+    let mut empreintes = Vec::new();
+    for trame in 0..trames {
+        while let Some(action) = actions.next_if(|action| action.trame <= trame) {
+            if action.appui {
+                gameboy.gerer_keydown(action.bouton);
+            } else {
+                gameboy.gerer_keyup(action.bouton);
+            }
+        }
+        let mut cycles = 0;
+        while cycles < CYCLES_PAR_TRAME {
+            cycles += gameboy.step();
+            if gameboy.has_screen_updated() {
+                break;
+            }
+        }
+        empreintes.push(Empreintes {
+            ecran: gameboy.get_hash_ecran(),
+            etat: gameboy.get_hash_etat(),
+        });
+    }
+    empreintes
+}
+
+// Première trame dont les empreintes diffèrent de la référence.
+pub fn comparer(obtenues: &[Empreintes], attendues: &[Empreintes]) -> Option<usize> {
+    (0..obtenues.len().max(attendues.len())).find(|&i| obtenues.get(i) != attendues.get(i))
+}
@@ -0,0 +1,86 @@
+use crate::empreinte::Empreinte;
+use crate::memoire::Memoire;
+use crate::mmu::InterruptFlag;
+
+// Port série sans câble branché : chaque octet envoyé est conservé dans `sortie` (les ROMs de test
+// y écrivent leurs résultats) et l'octet reçu vaut 0xFF.
+pub struct Serie {
+    // SB : décalé d'un bit vers la gauche à chaque front d'horloge, le bit reçu entrant à droite.
+    donnee: u8,
+    // SC : bit 7 transfert en cours, bit 1 horloge rapide (CGB), bit 0 horloge interne.
+    controle: u8,
+    // Bits envoyés depuis le début du transfert, et leur nombre.
+    envoye: u8,
+    bits: u8,
+    pub sortie: Vec<u8>,
+    pub interrupt: u8,
+}
+
+impl Serie {
+    pub fn new() -> Serie {
+        Serie {
+            donnee: 0x00,
+            controle: 0x00,
+            envoye: 0x00,
+            bits: 0,
+            sortie: Vec::new(),
+            interrupt: InterruptFlag::None as u8,
+        }
+    }
+
+    // Durée en cycles d'un bit du transfert en cours, None s'il n'y en a pas. Seule l'horloge
+    // interne fait avancer le transfert : 8192 Hz, ou 262144 Hz en horloge rapide.
+    pub fn get_duree_bit(&self) -> Option<u32> {
+        if self.controle & 0x81 != 0x81 {
+            return None;
+        }
+        if self.controle & 0x02 != 0 {
+            Some(16)
+        } else {
+            Some(512)
+        }
+    }
+
+    // La sortie, lue par l'hôte, ne fait pas partie de l'état de la console.
+    pub fn empreinte(&self, empreinte: &mut Empreinte) {
+        empreinte.ecrire(&[self.donnee, self.controle, self.envoye, self.bits, self.interrupt]);
+    }
+
+    // Une écriture dans SC qui démarre un transfert repart du premier bit.
+    pub fn demarrer_transfert(&mut self) {
+        self.envoye = 0x00;
+        self.bits = 0;
+    }
+
+    // Envoie le bit de poids fort de SB et reçoit un 1, faute de câble. Le transfert se termine
+    // au huitième bit.
+    pub fn transferer_bit(&mut self) {
+        self.envoye = (self.envoye << 1) | (self.donnee >> 7);
+        self.donnee = (self.donnee << 1) | 0x01;
+        self.bits += 1;
+        if self.bits < 8 {
+            return;
+        }
+        self.sortie.push(self.envoye);
+        self.controle &= 0x7F;
+        self.interrupt |= InterruptFlag::Serial as u8;
+    }
+}
+
+impl Memoire for Serie {
+    fn get_octet(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF01 => self.donnee,
+            0xFF02 => 0x7C | self.controle,
+            _ => 0x00,
+        }
+    }
+
+    fn set_octet(&mut self, addr: u16, value: u8) {
+        match addr {
+            0xFF01 => self.donnee = value,
+            0xFF02 => self.controle = value & 0x83,
+            _ => {}
+        }
+    }
+}
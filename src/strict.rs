@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::mem::{self, Discriminant};
+
+// Comportements illégaux signalés en mode strict. Ils ne sont pas bloquants sur le matériel
+// mais révèlent presque toujours un bug dans le programme.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Diagnostic {
+    // Écriture dans la ROM d'une cartouche sans MBC.
+    EcritureRom { addr: u16, value: u8 },
+    // Accès à la VRAM pendant le mode 3.
+    AccesVram { addr: u16, mode: u8 },
+    // Accès à l'OAM pendant les modes 2 et 3.
+    AccesOam { addr: u16, mode: u8 },
+    // Lecture de la RAM de la cartouche alors qu'elle est désactivée ou absente.
+    LectureRamDesactivee { addr: u16 },
+    // Lecture de la WRAM ou de la HRAM avant toute écriture.
+    LectureNonInitialisee { addr: u16 },
+    // Le pointeur de pile sort de la WRAM et de la HRAM.
+    PileHorsRam { sp: u16 },
+    // Accès à un registre inexistant du PPU.
+    RegistreInexistant { addr: u16 },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::EcritureRom { addr, value } => {
+                write!(f, "écriture en ROM sans MBC ${:04X} = ${:02X}", addr, value)
+            }
+            Diagnostic::AccesVram { addr, mode } => {
+                write!(f, "accès à la VRAM ${:04X} en mode {}", addr, mode)
+            }
+            Diagnostic::AccesOam { addr, mode } => {
+                write!(f, "accès à l'OAM ${:04X} en mode {}", addr, mode)
+            }
+            Diagnostic::LectureRamDesactivee { addr } => {
+                write!(f, "lecture de la RAM cartouche désactivée ${:04X}", addr)
+            }
+            Diagnostic::LectureNonInitialisee { addr } => {
+                write!(f, "lecture de la mémoire non initialisée ${:04X}", addr)
+            }
+            Diagnostic::PileHorsRam { sp } => write!(f, "pile hors de la RAM SP=${:04X}", sp),
+            Diagnostic::RegistreInexistant { addr } => {
+                write!(f, "accès au registre inexistant ${:04X}", addr)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Avertissement {
+    pub pc: u16,
+    pub bank: usize,
+    pub diagnostic: Diagnostic,
+}
+
+const WRAM_SIZE: usize = 0x8000;
+const HRAM_SIZE: usize = 0x7F;
+
+pub struct Strict {
+    // Instruction en cours d'exécution, renseignée par la Gameboy avant chaque step.
+    pub pc: u16,
+    pub bank: usize,
+    wram_init: Vec<bool>,
+    hram_init: [bool; HRAM_SIZE],
+    // Un même diagnostic n'est signalé qu'une fois par instruction.
+    signales: HashSet<(u16, usize, Discriminant<Diagnostic>)>,
+    avertissements: Vec<Avertissement>,
+}
+
+impl Strict {
+    pub fn new() -> Strict {
+        Strict {
+            pc: 0x0100,
+            bank: 0,
+            wram_init: vec![false; WRAM_SIZE],
+            hram_init: [false; HRAM_SIZE],
+            signales: HashSet::new(),
+            avertissements: Vec::new(),
+        }
+    }
+
+    pub fn signaler(&mut self, diagnostic: Diagnostic) {
+        if self
+            .signales
+            .insert((self.pc, self.bank, mem::discriminant(&diagnostic)))
+        {
+            self.avertissements.push(Avertissement {
+                pc: self.pc,
+                bank: self.bank,
+                diagnostic,
+            });
+        }
+    }
+
+    pub fn take_avertissements(&mut self) -> Vec<Avertissement> {
+        mem::take(&mut self.avertissements)
+    }
+
+    pub fn is_wram_init(&self, index: usize) -> bool {
+        self.wram_init[index]
+    }
+
+    pub fn set_wram_init(&mut self, index: usize) {
+        self.wram_init[index] = true;
+    }
+
+    pub fn is_hram_init(&self, index: usize) -> bool {
+        self.hram_init[index]
+    }
+
+    pub fn set_hram_init(&mut self, index: usize) {
+        self.hram_init[index] = true;
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::{BTreeMap, HashMap};
+
+// Table des symboles d'un fichier .sym produit par RGBDS (rgblink -n).
+// Chaque ligne associe "banque:adresse" à un label :
+//  00:0150 Main
+//  01:4000 PlayerUpdate
+//  ; les commentaires commencent par un point-virgule
+pub struct Symboles {
+    labels: BTreeMap<(usize, u16), String>,
+    adresses: HashMap<String, (usize, u16)>,
+}
+
+impl Default for Symboles {
+    fn default() -> Symboles {
+        Symboles::new()
+    }
+}
+
+impl Symboles {
+    pub fn new() -> Symboles {
+        Symboles {
+            labels: BTreeMap::new(),
+            adresses: HashMap::new(),
+        }
+    }
+
+    pub fn parse(contenu: &str) -> Symboles {
+        let mut symboles = Symboles::new();
+        for ligne in contenu.lines() {
+            let ligne = match ligne.split_once(';') {
+                Some((avant, _)) => avant,
+                None => ligne,
+            };
+            let mut champs = ligne.split_whitespace();
+            let (Some(position), Some(label)) = (champs.next(), champs.next()) else {
+                continue;
+            };
+            let Some((bank, addr)) = position.split_once(':') else {
+                continue;
+            };
+            let (Ok(bank), Ok(addr)) = (
+                usize::from_str_radix(bank, 16),
+                u16::from_str_radix(addr, 16),
+            ) else {
+                continue;
+            };
+            symboles.ajouter(bank, addr, label);
+        }
+        symboles
+    }
+
+    pub fn ajouter(&mut self, bank: usize, addr: u16, label: &str) {
+        // Le premier label d'une adresse est celui affiché, les suivants restent utilisables par nom.
+        self.labels
+            .entry((bank, addr))
+            .or_insert_with(|| label.to_string());
+        self.adresses.insert(label.to_string(), (bank, addr));
+    }
+
+    pub fn get_adresse(&self, label: &str) -> Option<(usize, u16)> {
+        self.adresses.get(label).copied()
+    }
+
+    // Label exact d'une adresse. Hors des zones de la cartouche, la banque n'est pas
+    // connue de façon fiable (WRAM, HRAM) et le label est cherché dans toutes les banques.
+    pub fn get_label(&self, bank: usize, addr: u16) -> Option<&str> {
+        if let Some(label) = self.labels.get(&(bank, addr)) {
+            return Some(label);
+        }
+        match addr {
+            0x4000..=0x7FFF | 0xA000..=0xBFFF => None,
+            _ => self
+                .labels
+                .iter()
+                .find(|((_, a), _)| *a == addr)
+                .map(|(_, label)| label.as_str()),
+        }
+    }
+
+    // Label le plus proche précédant une adresse de la ROM, avec le décalage : "PlayerUpdate+$12".
+    pub fn get_label_proche(&self, bank: usize, addr: u16) -> Option<String> {
+        if let Some(label) = self.get_label(bank, addr) {
+            return Some(label.to_string());
+        }
+        let debut = match addr {
+            0x0000..=0x3FFF => 0x0000,
+            0x4000..=0x7FFF => 0x4000,
+            _ => return None,
+        };
+        self.labels
+            .range((bank, debut)..(bank, addr))
+            .next_back()
+            .map(|((_, a), label)| format!("{}+${:X}", label, addr - a))
+    }
+
+    // Position dans le code : "BB:AAAA" suivi du label le plus proche s'il existe.
+    pub fn formater(&self, bank: usize, addr: u16) -> String {
+        match self.get_label_proche(bank, addr) {
+            Some(label) => format!("{:02X}:{:04X} {}", bank, addr, label),
+            None => format!("{:02X}:{:04X}", bank, addr),
+        }
+    }
+}
@@ -1,10 +1,19 @@
+#[allow(clippy::module_inception)]
 pub mod timer {
-    use crate::memoire::Memoire;
+    use crate::empreinte::Empreinte;
     use crate::mmu::InterruptFlag;
 
+    // Bit du compteur interne dont le front descendant incrémente TIMA, selon TAC.
+    //   00 : 4096 Hz     (bit 9)
+    //   01 : 262144 Hz   (bit 3)
+    //   10 : 65536 Hz    (bit 5)
+    //   11 : 16384 Hz    (bit 7)
+    const TAC_BITS: [u16; 4] = [9, 3, 5, 7];
+
     #[derive(Debug, Copy, Clone)]
     struct Registers {
-        div: u8,
+        // DIV correspond aux 8 bits de poids fort de ce compteur incrémenté à chaque cycle.
+        compteur: u16,
         tima: u8,
         tma: u8,
         tac: u8,
@@ -13,7 +22,7 @@ pub mod timer {
     impl Registers {
         pub fn new() -> Self {
             Self {
-                div: 0x00,
+                compteur: 0x0000,
                 tima: 0x00,
                 tma: 0x00,
                 tac: 0x00,
@@ -21,9 +30,16 @@ pub mod timer {
         }
     }
 
+    // Le timer n'est pas exécuté cycle par cycle : son état est calculé à partir du temps écoulé
+    // depuis la dernière synchronisation, et le planificateur ne le réveille qu'au rechargement de TIMA.
     #[derive(Debug, Copy, Clone)]
     pub struct Timer {
         registers: Registers,
+        // Instant, en cycles d'horloge, auquel les registres sont à jour.
+        synchro: u64,
+        // Après un dépassement, TIMA vaut 0 pendant un cycle machine avant d'être rechargé avec TMA :
+        // instant du rechargement.
+        rechargement: Option<u64>,
         pub interrupt: u8,
     }
 
@@ -31,39 +47,121 @@ pub mod timer {
         pub fn new() -> Self {
             Self {
                 registers: Registers::new(),
+                synchro: 0,
+                rechargement: None,
                 interrupt: InterruptFlag::None as u8,
             }
         }
 
-        pub fn run_cycles(&mut self, cycles: u32) {
-            if self.registers.tac & 0x04 != 0 {
-                for _ in 0..cycles {
-                    self.registers.tima = self.registers.tima.wrapping_add(1);
-                    if self.registers.tima == 0 {
-                        self.registers.tima = self.registers.tma;
-                        self.interrupt |= InterruptFlag::Timer as u8;
-                    }
+        fn get_signal(&self) -> bool {
+            let bit = TAC_BITS[(self.registers.tac & 0x03) as usize];
+            self.registers.tac & 0x04 != 0 && self.registers.compteur & (1 << bit) != 0
+        }
+
+        // Nombre de cycles entre deux fronts descendants du bit observé, si le timer est actif.
+        fn get_periode(&self) -> Option<u64> {
+            if self.registers.tac & 0x04 == 0 {
+                return None;
+            }
+            Some(1 << (TAC_BITS[(self.registers.tac & 0x03) as usize] + 1))
+        }
+
+        // Délai jusqu'au n-ième front descendant (n >= 1) depuis la synchronisation.
+        fn get_delai_fronts(&self, periode: u64, n: u64) -> u64 {
+            periode - u64::from(self.registers.compteur) % periode + (n - 1) * periode
+        }
+
+        fn inc_tima(&mut self) {
+            let (tima, overflow) = self.registers.tima.overflowing_add(1);
+            self.registers.tima = tima;
+            if overflow {
+                self.rechargement = Some(self.synchro + 4);
+            }
+        }
+
+        // Met les registres à jour à l'instant donné. Le planificateur garantit que le rechargement
+        // est traité avant que le temps ne le dépasse.
+        pub fn synchroniser(&mut self, maintenant: u64) {
+            let cycles = maintenant - self.synchro;
+            if cycles == 0 {
+                return;
+            }
+            if let (Some(periode), None) = (self.get_periode(), self.rechargement) {
+                let compteur = u64::from(self.registers.compteur);
+                let fronts = (compteur + cycles) / periode - compteur / periode;
+                let restants = 0x100 - u64::from(self.registers.tima);
+                if fronts >= restants {
+                    self.rechargement =
+                        Some(self.synchro + self.get_delai_fronts(periode, restants) + 4);
+                    self.registers.tima = 0x00;
+                } else {
+                    self.registers.tima += fronts as u8;
                 }
             }
+            self.registers.compteur = self.registers.compteur.wrapping_add(cycles as u16);
+            self.synchro = maintenant;
+        }
+
+        // Instant du prochain rechargement de TIMA, None si le timer est arrêté.
+        pub fn get_echeance(&self) -> Option<u64> {
+            if self.rechargement.is_some() {
+                return self.rechargement;
+            }
+            let periode = self.get_periode()?;
+            let restants = 0x100 - u64::from(self.registers.tima);
+            Some(self.synchro + self.get_delai_fronts(periode, restants) + 4)
+        }
+
+        pub fn recharger(&mut self, maintenant: u64) {
+            self.synchroniser(maintenant);
+            self.rechargement = None;
+            self.registers.tima = self.registers.tma;
+            self.interrupt |= InterruptFlag::Timer as u8;
         }
-    }
 
-    impl Memoire for Timer {
-        fn get_octet(&self, addr: u16) -> u8 {
+        // Une écriture dans DIV ou TAC qui fait passer le signal à 0 incrémente TIMA.
+        fn set_signal<F: FnOnce(&mut Registers)>(&mut self, f: F) {
+            let signal = self.get_signal();
+            f(&mut self.registers);
+            if signal && !self.get_signal() {
+                self.inc_tima();
+            }
+        }
+
+        // L'empreinte porte sur les registres projetés à l'instant donné, pour ne pas dépendre
+        // de la date de la dernière synchronisation.
+        pub fn empreinte(&self, empreinte: &mut Empreinte, maintenant: u64) {
+            let mut timer = *self;
+            timer.synchroniser(maintenant);
+            empreinte.ecrire_u16(timer.registers.compteur);
+            empreinte.ecrire(&[timer.registers.tima, timer.registers.tma, timer.registers.tac]);
+            empreinte.ecrire_u64(timer.rechargement.unwrap_or(u64::MAX));
+            empreinte.ecrire_u8(timer.interrupt);
+        }
+
+        pub fn get_octet(&self, addr: u16, maintenant: u64) -> u8 {
+            let mut timer = *self;
+            timer.synchroniser(maintenant);
             match addr {
-                0xFF04 => self.registers.div,
-                0xFF05 => self.registers.tima,
-                0xFF06 => self.registers.tma,
-                0xFF07 => self.registers.tac,
+                0xFF04 => (timer.registers.compteur >> 8) as u8,
+                0xFF05 => timer.registers.tima,
+                0xFF06 => timer.registers.tma,
+                0xFF07 => 0xF8 | timer.registers.tac,
                 _ => 0x00,
             }
         }
 
-        fn set_octet(&mut self, addr: u16, value: u8) {
+        pub fn set_octet(&mut self, addr: u16, value: u8, maintenant: u64) {
+            self.synchroniser(maintenant);
             match addr {
-                0xFF04 => self.registers.div = 0x00,
-                0xFF05 => self.registers.tima = value,
+                0xFF04 => self.set_signal(|registers| registers.compteur = 0x0000),
+                0xFF05 => {
+                    // Écrire TIMA pendant le délai de rechargement l'annule.
+                    self.rechargement = None;
+                    self.registers.tima = value;
+                }
                 0xFF06 => self.registers.tma = value,
+                0xFF07 => self.set_signal(|registers| registers.tac = value & 0x07),
                 _ => {}
             }
         }
@@ -0,0 +1,62 @@
+// Allumage et extinction de l'écran par LCDC bit 7, vus par le frontend. La ROM boucle sur un
+// `jr -2` : seul le PPU travaille.
+
+use emulateur_gameboy::Gameboy;
+
+const CYCLES_PAR_TRAME: u32 = 70224;
+
+fn creer_gameboy() -> Gameboy {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0100] = 0x18;
+    rom[0x0101] = 0xFE;
+    let mut gameboy = Gameboy::new(rom);
+    // Fond noir, pour distinguer une trame rendue de l'écran blanc.
+    gameboy.set_octet_memoire(0xFF47, 0xFF);
+    gameboy
+}
+
+// Exécute une trame et indique si une image a été signalée.
+fn executer_trame(gameboy: &mut Gameboy) -> bool {
+    let mut cycles = 0;
+    let mut rafraichie = false;
+    while cycles < CYCLES_PAR_TRAME {
+        cycles += gameboy.step();
+        rafraichie |= gameboy.has_screen_updated();
+    }
+    rafraichie
+}
+
+fn is_ecran_blanc(gameboy: &Gameboy) -> bool {
+    gameboy
+        .get_screen_data()
+        .iter()
+        .all(|pixel| (pixel.r, pixel.g, pixel.b) == (0xFF, 0xFF, 0xFF))
+}
+
+// Écran éteint : LY et le mode de STAT valent 0 et aucune trame n'est signalée.
+#[test]
+fn ecran_eteint() {
+    let mut gameboy = creer_gameboy();
+    executer_trame(&mut gameboy);
+    gameboy.set_octet_memoire(0xFF40, 0x11);
+    assert!(!gameboy.is_ecran_allume());
+    for _ in 0..3 {
+        assert!(!executer_trame(&mut gameboy));
+        assert_eq!(gameboy.get_octet_memoire(0xFF44), 0);
+        assert_eq!(gameboy.get_octet_memoire(0xFF41) & 0x03, 0);
+        assert!(is_ecran_blanc(&gameboy));
+    }
+}
+
+// La première trame après l'allumage n'est ni signalée ni affichée.
+#[test]
+fn premiere_trame_masquee() {
+    let mut gameboy = creer_gameboy();
+    gameboy.set_octet_memoire(0xFF40, 0x11);
+    executer_trame(&mut gameboy);
+    gameboy.set_octet_memoire(0xFF40, 0x91);
+    assert!(!executer_trame(&mut gameboy));
+    assert!(is_ecran_blanc(&gameboy));
+    assert!(executer_trame(&mut gameboy));
+    assert!(!is_ecran_blanc(&gameboy));
+}
@@ -0,0 +1,44 @@
+// Non-régression : les empreintes de chaque trame doivent rester celles enregistrées dans
+// tests/regression. Après un changement voulu du rendu ou du timing, la référence est régénérée avec
+//   cargo run --release --bin regression -- roms/tetris.gb tests/regression/tetris.reference \
+//       --script tests/regression/tetris.script --trames 900 --enregistrer
+// Tetris ne modifie pas les registres en cours de ligne : le rendu FIFO doit donner les mêmes trames.
+
+use std::fs;
+use std::path::PathBuf;
+
+use emulateur_gameboy::regression;
+use emulateur_gameboy::{Gameboy, Rendu};
+
+const TRAMES: u32 = 900;
+
+fn verifier(rendu: Rendu, reference: &str) {
+    let racine = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let rom = fs::read(racine.join("roms/tetris.gb")).unwrap();
+    let script = fs::read_to_string(racine.join("tests/regression/tetris.script")).unwrap();
+    let reference = fs::read_to_string(racine.join("tests/regression").join(reference)).unwrap();
+    let script = regression::parse_script(&script).unwrap();
+    let attendues = regression::parse_reference(&reference).unwrap();
+
+    let mut gameboy = Gameboy::new(rom);
+    gameboy.set_rendu(rendu);
+    let obtenues = regression::executer(&mut gameboy, &script, TRAMES);
+    if let Some(trame) = regression::comparer(&obtenues, &attendues) {
+        panic!(
+            "regression: divergence à la trame {} : {:x?} au lieu de {:x?}",
+            trame,
+            obtenues.get(trame),
+            attendues.get(trame)
+        );
+    }
+}
+
+#[test]
+fn tetris() {
+    verifier(Rendu::Ligne, "tetris.reference");
+}
+
+#[test]
+fn tetris_fifo() {
+    verifier(Rendu::Fifo, "tetris.reference");
+}
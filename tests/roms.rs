@@ -0,0 +1,225 @@
+// Suite de conformité : chaque ROM (.gb, .gbc) trouvée dans test-roms/, ou dans $TEST_ROMS, est exécutée
+// sans fenêtre et son résultat est détecté automatiquement :
+//  - Blargg (cpu_instrs, instr_timing, mem_timing) : "Passed" ou "Failed" sur le port série ;
+//  - Mooneye : registres B, C, D, E, H, L = 3, 5, 8, 13, 21, 34 (suite de Fibonacci) au `ld b, b` final,
+//    0x42 partout en cas d'échec ;
+//  - dmg-acid2, cgb-acid2 : l'écran au `ld b, b` final est comparé à la capture de référence placée à
+//    côté de la ROM (jeu.gb -> jeu.png ou jeu-reference.png).
+// Le test, ignoré par défaut, échoue si aucune ROM n'est trouvée : cargo test -- --ignored.
+
+use std::env;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use emulateur_gameboy::{Gameboy, Status};
+
+const CYCLES_PAR_SECONDE: u64 = 4_194_304;
+// Durée maximale d'un test : 120 secondes émulées.
+const CYCLES_MAX: u64 = 120 * CYCLES_PAR_SECONDE;
+
+const CYCLES_PAR_TRAME: u32 = 70224;
+
+const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+enum Resultat {
+    Reussi,
+    Echec(String),
+    Delai,
+}
+
+fn get_repertoire() -> PathBuf {
+    match env::var_os("TEST_ROMS") {
+        Some(repertoire) => PathBuf::from(repertoire),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-roms"),
+    }
+}
+
+fn chercher_roms(repertoire: &Path, roms: &mut Vec<PathBuf>) {
+    let Ok(entrees) = fs::read_dir(repertoire) else {
+        return;
+    };
+    for chemin in entrees.filter_map(|entree| entree.ok().map(|entree| entree.path())) {
+        if chemin.is_dir() {
+            chercher_roms(&chemin, roms);
+        } else if chemin
+            .extension()
+            .is_some_and(|extension| extension == "gb" || extension == "gbc")
+        {
+            roms.push(chemin);
+        }
+    }
+}
+
+fn chercher_reference(rom: &Path) -> Option<PathBuf> {
+    let nom = rom.file_stem()?.to_string_lossy();
+    [format!("{}.png", nom), format!("{}-reference.png", nom)]
+        .iter()
+        .map(|fichier| rom.with_file_name(fichier))
+        .find(|chemin| chemin.exists())
+}
+
+// Les captures de référence n'utilisent pas forcément les mêmes nuances que l'émulateur :
+// chaque pixel est ramené à l'une des 4 nuances de gris, du blanc au noir.
+fn get_nuance(r: u8, g: u8, b: u8) -> u8 {
+    match (u16::from(r) + u16::from(g) + u16::from(b)) / 3 {
+        0xD0.. => 0,
+        0x80.. => 1,
+        0x30.. => 2,
+        _ => 3,
+    }
+}
+
+fn charger_reference(chemin: &Path) -> Result<Vec<u8>, String> {
+    let fichier = fs::File::open(chemin).map_err(|erreur| erreur.to_string())?;
+    let mut decodeur = png::Decoder::new(fichier);
+    decodeur.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut lecteur = decodeur.read_info().map_err(|erreur| erreur.to_string())?;
+    let mut image = vec![0; lecteur.output_buffer_size()];
+    let info = lecteur
+        .next_frame(&mut image)
+        .map_err(|erreur| erreur.to_string())?;
+    let octets_par_pixel = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("png indexé non développé".to_string()),
+    };
+    Ok(image[..info.buffer_size()]
+        .chunks(octets_par_pixel)
+        .map(|pixel| match octets_par_pixel {
+            1 | 2 => get_nuance(pixel[0], pixel[0], pixel[0]),
+            _ => get_nuance(pixel[0], pixel[1], pixel[2]),
+        })
+        .collect())
+}
+
+fn comparer_ecran(gameboy: &mut Gameboy, reference: &Path) -> Resultat {
+    let reference = match charger_reference(reference) {
+        Ok(reference) => reference,
+        Err(erreur) => return Resultat::Echec(format!("référence illisible : {}", erreur)),
+    };
+    // L'image affichée est celle de la trame qui suit le `ld b, b`, écran éteint compris.
+    gameboy.has_screen_updated();
+    let mut cycles = 0;
+    while !gameboy.has_screen_updated() && cycles < 2 * CYCLES_PAR_TRAME {
+        cycles += gameboy.step();
+    }
+    let ecran: Vec<u8> = gameboy
+        .get_screen_data()
+        .iter()
+        .map(|pixel| get_nuance(pixel.r, pixel.g, pixel.b))
+        .collect();
+    if ecran.len() != reference.len() {
+        return Resultat::Echec("taille de la référence différente de l'écran".to_string());
+    }
+    match ecran.iter().zip(&reference).filter(|(a, b)| a != b).count() {
+        0 => Resultat::Reussi,
+        differences => Resultat::Echec(format!("{} pixels différents", differences)),
+    }
+}
+
+fn executer_rom(chemin: &Path) -> Resultat {
+    let rom = match fs::read(chemin) {
+        Ok(rom) => rom,
+        Err(erreur) => return Resultat::Echec(erreur.to_string()),
+    };
+    let reference = chercher_reference(chemin);
+    let mut gameboy = Gameboy::new(rom);
+    gameboy.set_vitesse_limitee(false);
+    gameboy.set_breakpoints_logiciels(true);
+    let mut serie = String::new();
+    // Blargg détaille l'échec après "Failed" : la sortie est encore lue pendant une seconde.
+    let mut fin_echec = None;
+    let mut cycles = 0;
+    while cycles < CYCLES_MAX {
+        if fin_echec.is_some_and(|fin| cycles >= fin) {
+            return Resultat::Echec(serie.split_whitespace().collect::<Vec<&str>>().join(" "));
+        }
+        cycles += u64::from(gameboy.step());
+        if let Status::LockedUp { pc, opcode } = gameboy.get_status() {
+            return Resultat::Echec(format!("cpu bloqué par ${:02X} à ${:04X}", opcode, pc));
+        }
+        let octets = gameboy.take_sortie_serie();
+        if !octets.is_empty() {
+            serie += &String::from_utf8_lossy(&octets);
+            if serie.contains("Passed") {
+                return Resultat::Reussi;
+            }
+            if serie.contains("Failed") && fin_echec.is_none() {
+                fin_echec = Some(cycles + CYCLES_PAR_SECONDE);
+            }
+        }
+        if gameboy.take_breakpoint_logiciel() {
+            if let Some(reference) = &reference {
+                return comparer_ecran(&mut gameboy, reference);
+            }
+            let registres = gameboy.get_registres();
+            let signature = [
+                registres.b,
+                registres.c,
+                registres.d,
+                registres.e,
+                registres.h,
+                registres.l,
+            ];
+            if signature == FIBONACCI {
+                return Resultat::Reussi;
+            }
+            if signature == [0x42; 6] {
+                return Resultat::Echec("signature d'échec Mooneye".to_string());
+            }
+        }
+    }
+    Resultat::Delai
+}
+
+#[test]
+#[ignore = "ROMs de test externes, voir $TEST_ROMS"]
+fn roms_de_test() {
+    let repertoire = get_repertoire();
+    let mut roms = Vec::new();
+    chercher_roms(&repertoire, &mut roms);
+    assert!(!roms.is_empty(), "roms: aucune ROM dans {}", repertoire.display());
+    roms.sort();
+
+    // Les ROMs sont réparties entre les threads, chaque Gameboy étant créée dans le sien.
+    let suivante = AtomicUsize::new(0);
+    let resultats = Mutex::new(Vec::new());
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = suivante.fetch_add(1, Ordering::Relaxed);
+                let Some(rom) = roms.get(index) else {
+                    break;
+                };
+                let resultat = panic::catch_unwind(AssertUnwindSafe(|| executer_rom(rom)))
+                    .unwrap_or_else(|_| Resultat::Echec("panique de l'émulateur".to_string()));
+                resultats.lock().unwrap().push((index, resultat));
+            });
+        }
+    });
+    let mut resultats = resultats.into_inner().unwrap();
+    resultats.sort_by_key(|(index, _)| *index);
+
+    let mut echecs = 0;
+    for (index, resultat) in &resultats {
+        let nom = roms[*index].strip_prefix(&repertoire).unwrap_or(&roms[*index]);
+        let (statut, detail) = match resultat {
+            Resultat::Reussi => ("ok", String::new()),
+            Resultat::Echec(detail) => ("ÉCHEC", detail.clone()),
+            Resultat::Delai => ("DÉLAI", "aucun résultat après 120 s émulées".to_string()),
+        };
+        if !matches!(resultat, Resultat::Reussi) {
+            echecs += 1;
+        }
+        println!("{:<60} {:<6} {}", nom.display(), statut, detail);
+    }
+    println!("{}/{} ROMs réussies", resultats.len() - echecs, resultats.len());
+    assert_eq!(echecs, 0, "roms: {} ROMs en échec", echecs);
+}